@@ -1,25 +1,40 @@
+use std::collections::BTreeSet;
 use std::sync::Arc;
-use std::sync::atomic::AtomicBool;
 use std::sync::mpsc::{Receiver, Sender};
-use std::thread::JoinHandle;
 use std::time::Duration;
 
-use crate::{CommandMessage, Message, Mode, Pod, ResultMessage, Search, StatefulList};
+use chrono::{DateTime, Utc};
+use tui::widgets::ListState;
+
+use crate::{CommandMessage, Level, Message, Mode, Pod, ResultMessage, Search, StatefulList};
+use crate::pod::cluster::KubeTarget;
+use crate::args::SourceOptions;
+use crate::config::Config;
+use crate::source::{configure, LogSource};
+use crate::stack::StackView;
 
 /// App holds the state of the application
 pub struct App {
-    pub(crate) show_info: bool,
-    pub(crate) show_warn: bool,
-    pub(crate) show_debug: bool,
-    pub(crate) show_error: bool,
+    pub(crate) hidden_levels: BTreeSet<Level>,
     pub(crate) wrap: bool,
-    pub(crate) stops: Vec<Arc<AtomicBool>>,
+    pub(crate) show_fields: bool,
+    /// Shown in the title of the pick lists until the next key
+    pub(crate) error: Option<String>,
+    pub(crate) stacks: StackView,
+    /// First and last screen row and key of the messages shown
+    pub(crate) message_rows: Vec<(u16, u16, u64)>,
     pub(crate) dropped_bottom_messages: usize,
     pub(crate) last_message_height: usize,
     pub(crate) just_skipped: bool,
     pub(crate) just_skipped_bottom: bool,
-    pub(crate) handles: Vec<JoinHandle<()>>,
+    pub(crate) sources: Vec<Box<dyn LogSource>>,
+    pub(crate) sources_state: ListState,
+    pub(crate) diagnostics_scroll: u16,
     pub(crate) pods: StatefulList<Pod>,
+    pub(crate) target: KubeTarget,
+    pub(crate) config: Config,
+    /// Options for the sources picked in the UI
+    pub(crate) options: SourceOptions,
     pub(crate) input: Vec<char>,
     pub(crate) mode: Mode,
     pub(crate) input_index: usize,
@@ -27,6 +42,10 @@ pub struct App {
     pub(crate) skip: usize,
     pub(crate) size: u64,
     pub(crate) length: usize,
+    /// Messages dropped for the memory budget
+    pub(crate) evicted: usize,
+    /// The oldest message kept once messages were dropped
+    pub(crate) oldest: Option<DateTime<Utc>>,
     pub(crate) elapsed: Duration,
     pub(crate) window_size: u16,
     pub(crate) tx: Sender<CommandMessage>,
@@ -37,29 +56,102 @@ pub struct App {
 impl App {
     pub fn default(tx: Sender<CommandMessage>, rx_result: Receiver<ResultMessage>) -> App {
         App {
-            show_info: true,
-            show_warn: true,
-            show_debug: true,
-            show_error: true,
+            hidden_levels: BTreeSet::new(),
             dropped_bottom_messages: 0,
             last_message_height: 0,
             just_skipped: false,
-            stops: Vec::new(),
-            handles: Vec::new(),
+            sources: Vec::new(),
+            sources_state: ListState::default(),
+            diagnostics_scroll: 0,
             pods: StatefulList::with_items(vec![]),
+            target: KubeTarget::default(),
+            config: Config::default(),
+            options: SourceOptions::default(),
             mode: Search,
             input: Vec::new(),
             input_index: 0,
             messages: Vec::new(),
             skip: 0,
             length: 0,
+            evicted: 0,
+            oldest: None,
             size: 0,
             elapsed: Duration::from_micros(0),
             window_size: 0,
             tx,
             rx_result,
             wrap: true,
+            show_fields: false,
+            error: None,
+            stacks: StackView::default(),
+            message_rows: Vec::new(),
             just_skipped_bottom: false,
         }
     }
+
+    pub fn add_source(&mut self, mut source: Box<dyn LogSource>) {
+        source.start(self.tx.clone());
+        self.sources.push(source);
+    }
+
+    /// Starts a source picked in the UI with the options from the end of the command line.
+    pub fn add_picked(&mut self, mut source: Box<dyn LogSource>) {
+        // The profile was checked on startup
+        let _ = configure(source.as_mut(), &self.options, &self.config);
+        self.add_source(source);
+    }
+
+    /// Stops and removes the pods and topics picked before, the sources given on the
+    /// command line keep running. Returns whether any others are left.
+    pub fn stop_picked(&mut self) -> bool {
+        self.sources.iter_mut().filter(|s| s.picked()).for_each(|s| s.stop());
+        self.sources.retain(|s| !s.picked());
+        self.sources_state.select(match self.sources.is_empty() {
+            true => { None }
+            false => { Some(0) }
+        });
+        !self.sources.is_empty()
+    }
+
+    pub fn stop_sources(&mut self) {
+        self.sources.iter_mut().for_each(|s| s.stop());
+        self.sources.clear();
+        self.sources_state = ListState::default();
+    }
+
+    pub fn selected_source(&mut self) -> Option<&mut Box<dyn LogSource>> {
+        let i = self.sources_state.selected()?;
+        self.sources.get_mut(i)
+    }
+
+    /// Switches the selected source to the next parser, a detected one counts as `auto`.
+    pub fn next_parser(&mut self) {
+        let current = match self.selected_source() {
+            Some(s) if s.state().detected() => { "auto".to_string() }
+            Some(s) => { s.state().parser().to_string() }
+            None => { return; }
+        };
+        let names = self.config.parser_names();
+        let next = names.iter().position(|n| *n == current).map(|i| (i + 1) % names.len()).unwrap_or(0);
+        if let Ok(parser) = self.config.parser(&names[next], None) {
+            if let Some(source) = self.selected_source() {
+                source.set_parser(Arc::new(parser));
+            }
+        }
+    }
+
+    /// Switches the selected source to the next profile.
+    pub fn next_profile(&mut self) {
+        let current = match self.selected_source() {
+            Some(s) => { s.state().mapping().name.clone() }
+            None => { return; }
+        };
+        let names = self.config.profile_names();
+        let next = names.iter().position(|n| *n == current).map(|i| (i + 1) % names.len()).unwrap_or(0);
+        if let Ok(mapping) = self.config.profile(&names[next]) {
+            if let Some(source) = self.selected_source() {
+                source.set_mapping(mapping);
+            }
+        }
+    }
 }
@@ -0,0 +1,47 @@
+use std::path::Path;
+
+use crate::pod::cluster::KubeTarget;
+use crate::source::file_set::FileSet;
+
+/// Command line arguments, `search [--context CONTEXT] [-n NAMESPACE] [--profile NAME] [--parser auto|json|logfmt|PATTERN] [-l SELECTOR] [--deployment NAME] [--rotated FILE] [FILE|GLOB]...`
+pub struct Args {
+    pub(crate) files: Vec<(String, SourceOptions)>,
+    pub(crate) file_sets: Vec<(FileSet, SourceOptions)>,
+    pub(crate) selectors: Vec<(String, SourceOptions)>,
+    pub(crate) deployments: Vec<(String, SourceOptions)>,
+    pub(crate) target: KubeTarget,
+    /// In effect at the end of the command line, for stdin and the sources picked later
+    pub(crate) options: SourceOptions,
+}
+
+/// Options that apply to the sources named after them on the command line
+#[derive(Clone, Default)]
+pub struct SourceOptions {
+    pub(crate) profile: Option<String>,
+    pub(crate) parser: Option<String>,
+}
+
+impl Args {
+    pub fn parse<I: Iterator<Item=String>>(mut args: I) -> Result<Args, String> {
+        let mut parsed = Args { files: Vec::new(), file_sets: Vec::new(), selectors: Vec::new(), deployments: Vec::new(), target: KubeTarget::default(), options: SourceOptions::default() };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-l" | "--selector" => { parsed.selectors.push((value(&arg, args.next())?, parsed.options.clone())) }
+                "--deployment" => { parsed.deployments.push((value(&arg, args.next())?, parsed.options.clone())) }
+                "--rotated" => { parsed.file_sets.push((FileSet::Rotated(value(&arg, args.next())?), parsed.options.clone())) }
+                "-n" | "--namespace" => { parsed.target.namespace = Some(value(&arg, args.next())?) }
+                "--context" => { parsed.target.context = Some(value(&arg, args.next())?) }
+                "--profile" => { parsed.options.profile = Some(value(&arg, args.next())?) }
+                "--parser" => { parsed.options.parser = Some(value(&arg, args.next())?) }
+                _ if arg.starts_with('-') => { return Err(format!("Unknown option {}", arg)); }
+                // A file named like a pattern, `app[1].log`, is read as it is
+                _ if arg.contains(['*', '?', '[']) && !Path::new(&arg).exists() => { parsed.file_sets.push((FileSet::Glob(arg), parsed.options.clone())) }
+                _ => { parsed.files.push((arg, parsed.options.clone())) }
+            }
+        }
+        Ok(parsed)
+    }
+}
+fn value(arg: &str, value: Option<String>) -> Result<String, String> {
+    value.ok_or_else(|| format!("{} needs a value", arg))
+}
@@ -0,0 +1,114 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::Level;
+use crate::detect::Candidates;
+use crate::mapping::FieldMapping;
+use crate::multiline::{Multiline, MultilineConfig};
+use crate::parse_send::Parser;
+use crate::pattern::{LinePattern, PatternConfig};
+use crate::search_thread::Budget;
+use crate::timestamp;
+use crate::timestamp::TimestampConfig;
+
+/// Read from the working directory when it exists
+pub const CONFIG_FILE: &str = "search.json";
+
+/// Settings from `search.json`
+#[derive(Deserialize, Default)]
+pub struct Config {
+    /// Field mappings by name, next to the built-in ones
+    #[serde(default)]
+    pub(crate) profiles: BTreeMap<String, FieldMapping>,
+    /// Regexes for plain text lines by name
+    #[serde(default, rename = "patterns")]
+    pattern_configs: BTreeMap<String, PatternConfig>,
+    #[serde(skip)]
+    pub(crate) patterns: Vec<Arc<LinePattern>>,
+    /// Which lines start an event in plain text logs
+    #[serde(default, rename = "multiline")]
+    multiline_config: Option<MultilineConfig>,
+    #[serde(skip)]
+    pub(crate) multiline: Arc<Multiline>,
+    /// More level names, like `"notice": "INFO"`
+    #[serde(default)]
+    pub(crate) levels: BTreeMap<String, Level>,
+    /// More formats and the timezone of timestamps without offset
+    #[serde(default)]
+    timestamps: TimestampConfig,
+    /// How many bytes or messages are kept before the oldest are dropped
+    #[serde(default)]
+    pub(crate) memory: Budget,
+}
+
+impl Config {
+    pub fn load() -> Result<Config, String> {
+        let text = match fs::read_to_string(CONFIG_FILE) {
+            Ok(t) => { t }
+            Err(e) if e.kind() == ErrorKind::NotFound => { return Ok(Config::default()); }
+            Err(e) => { return Err(format!("{}: {}", CONFIG_FILE, e)); }
+        };
+        let mut config: Config = serde_json::from_str(&text).map_err(|e| format!("{}: {}", CONFIG_FILE, e))?;
+        config.profiles.iter_mut().for_each(|(name, p)| p.name = name.clone());
+        config.patterns = config.pattern_configs.iter()
+            .map(|(name, p)| LinePattern::new(name, p).map(Arc::new))
+            .collect::<Result<_, _>>()?;
+        Level::set_aliases(&config.levels);
+        timestamp::configure(&config.timestamps)?;
+        if let Some(multiline) = &config.multiline_config {
+            config.multiline = Arc::new(Multiline::new(multiline)?);
+        }
+        Ok(config)
+    }
+
+    /// A profile from the config, or a built-in one with that name.
+    pub fn profile(&self, name: &str) -> Result<Arc<FieldMapping>, String> {
+        self.profiles.get(name).cloned()
+            .or_else(|| FieldMapping::builtin().into_iter().find(|p| p.name == name))
+            .map(Arc::new)
+            .ok_or_else(|| format!("Unknown profile {}, known are {}", name, self.profile_names().join(", ")))
+    }
+
+    /// One of `parser_names`, `auto` only picks `profile` for JSON when it is given.
+    pub fn parser(&self, name: &str, profile: Option<&str>) -> Result<Parser, String> {
+        match name {
+            "auto" => {
+                let profiles = match profile {
+                    Some(p) => { vec![self.profile(p)?] }
+                    None => { self.profile_names().iter().map(|p| self.profile(p)).collect::<Result<_, _>>()? }
+                };
+                Ok(Parser::Auto(Arc::new(Candidates { profiles, patterns: self.patterns.clone() })))
+            }
+            "json" => { Ok(Parser::Json) }
+            "logfmt" => { Ok(Parser::Logfmt) }
+            "raw" => { Ok(Parser::Raw) }
+            _ => {
+                self.patterns.iter().find(|p| p.name == name)
+                    .map(|p| Parser::Pattern(p.clone()))
+                    .ok_or_else(|| format!("Unknown parser {}, known are {}", name, self.parser_names().join(", ")))
+            }
+        }
+    }
+
+    pub fn parser_names(&self) -> Vec<String> {
+        let mut names = vec!["auto".to_string(), "json".to_string(), "logfmt".to_string()];
+        names.extend(self.patterns.iter().map(|p| p.name.clone()));
+        names.push("raw".to_string());
+        names
+    }
+
+    /// The built-in profiles followed by the configured ones.
+    pub fn profile_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = FieldMapping::builtin().into_iter().map(|p| p.name).collect();
+        for name in self.profiles.keys() {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+        names
+    }
+}
@@ -0,0 +1,78 @@
+use std::sync::Arc;
+
+use crate::mapping::FieldMapping;
+use crate::parse_send::{parse_json, parse_logfmt, Parser};
+use crate::pattern::LinePattern;
+
+/// How many lines a source on `auto` reads before it picks a parser
+pub const SAMPLE_SIZE: usize = 20;
+/// Fewer lines that no parser reads are no reason for `raw` yet
+pub const MIN_SAMPLES: usize = 5;
+
+/// What `auto` picks from, the first one wins a tie
+pub struct Candidates {
+    pub(crate) profiles: Vec<Arc<FieldMapping>>,
+    pub(crate) patterns: Vec<Arc<LinePattern>>,
+}
+
+impl Default for Candidates {
+    fn default() -> Candidates {
+        Candidates {
+            profiles: FieldMapping::builtin().into_iter().map(Arc::new).collect(),
+            patterns: Vec::new(),
+        }
+    }
+}
+
+/// The parser that reads most of the lines, with the profile for JSON. `raw` when
+/// none reads at least half of them.
+pub fn detect(candidates: &Candidates, lines: &[String], mapping: &FieldMapping) -> (Parser, Option<Arc<FieldMapping>>) {
+    let count = |f: &dyn Fn(&str) -> bool| lines.iter().filter(|l| f(l)).count();
+    let mut best = (0, Parser::Raw, None);
+    for profile in &candidates.profiles {
+        let read = count(&|l| parse_json(l, profile).is_ok());
+        if read > best.0 {
+            best = (read, Parser::Json, Some(profile.clone()));
+        }
+    }
+    for pattern in &candidates.patterns {
+        let read = count(&|l| pattern.parse(l).is_ok());
+        if read > best.0 {
+            best = (read, Parser::Pattern(pattern.clone()), None);
+        }
+    }
+    let read = count(&|l| parse_logfmt(l, mapping).is_ok());
+    if read > best.0 {
+        best = (read, Parser::Logfmt, None);
+    }
+    match best.0 * 2 >= lines.len() && best.0 > 0 {
+        true => { (best.1, best.2) }
+        false => { (Parser::Raw, None) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::detect::{Candidates, detect};
+    use crate::mapping::FieldMapping;
+    use crate::parse_send::Parser;
+
+    #[test]
+    fn picks_parser_and_profile() {
+        let default = FieldMapping::builtin().remove(0);
+        let lines = |l: &[&str]| l.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+        let (parser, profile) = detect(&Candidates::default(), &lines(&[
+            r#"{"ts": 1659838221.5, "level": "info", "msg": "ok"}"#,
+            r#"{"ts": 1659838222.5, "level": "warn", "msg": "slow"}"#,
+            "panic: boom",
+        ]), &default);
+        assert!(matches!(parser, Parser::Json));
+        assert_eq!(profile.unwrap().name, "zap");
+
+        let (parser, _) = detect(&Candidates::default(), &lines(&["ts=2022-08-07T02:10:21Z level=info msg=ok", "starting"]), &default);
+        assert!(matches!(parser, Parser::Logfmt));
+
+        let (parser, _) = detect(&Candidates::default(), &lines(&["starting", "listening on 8080", "ts=2022-08-07T02:10:21Z level=info msg=ok"]), &default);
+        assert!(matches!(parser, Parser::Raw));
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+
+/// How many of the last rejected lines are kept per reason
+const SAMPLES: usize = 5;
+const SAMPLE_LENGTH: usize = 300;
+
+/// Why a line couldn't be parsed
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub enum Reject {
+    NotJson,
+    NotLogfmt,
+    /// Neither JSON nor logfmt
+    NotRecognized,
+    NoPatternMatch,
+    NoTimestamp,
+    BadTimestamp,
+    NoMessage,
+    NoLevel,
+    UnknownLevel,
+}
+
+impl fmt::Display for Reject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Reject::NotJson => { write!(f, "not JSON") }
+            Reject::NotLogfmt => { write!(f, "not logfmt") }
+            Reject::NotRecognized => { write!(f, "neither JSON, a pattern nor logfmt") }
+            Reject::NoPatternMatch => { write!(f, "pattern doesn't match") }
+            Reject::NoTimestamp => { write!(f, "no timestamp") }
+            Reject::BadTimestamp => { write!(f, "timestamp in no known format") }
+            Reject::NoMessage => { write!(f, "no message") }
+            Reject::NoLevel => { write!(f, "no level") }
+            Reject::UnknownLevel => { write!(f, "unknown level") }
+        }
+    }
+}
+
+/// The lines a source couldn't parse, counted by reason
+#[derive(Default, Clone)]
+pub struct Diagnostics {
+    reasons: BTreeMap<Reject, Rejected>,
+}
+
+#[derive(Default, Clone)]
+pub struct Rejected {
+    pub(crate) count: usize,
+    pub(crate) samples: VecDeque<String>,
+}
+
+impl Diagnostics {
+    pub fn add(&mut self, reason: Reject, line: &str) {
+        let rejected = self.reasons.entry(reason).or_default();
+        rejected.count += 1;
+        if rejected.samples.len() == SAMPLES {
+            rejected.samples.pop_front();
+        }
+        rejected.samples.push_back(line.chars().take(SAMPLE_LENGTH).collect());
+    }
+
+    pub fn total(&self) -> usize {
+        self.reasons.values().map(|r| r.count).sum()
+    }
+
+    pub fn reasons(&self) -> impl Iterator<Item=(&Reject, &Rejected)> {
+        self.reasons.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostics::{Diagnostics, Reject, SAMPLE_LENGTH, SAMPLES};
+
+    #[test]
+    fn counts_and_keeps_the_last_samples() {
+        let mut diagnostics = Diagnostics::default();
+        (0..SAMPLES + 2).for_each(|i| diagnostics.add(Reject::NotJson, &format!("line {}", i)));
+        diagnostics.add(Reject::NoLevel, &"x".repeat(SAMPLE_LENGTH + 10));
+        assert_eq!(diagnostics.total(), SAMPLES + 3);
+        let reasons: Vec<_> = diagnostics.reasons().collect();
+        assert!(*reasons[0].0 == Reject::NotJson);
+        assert_eq!(reasons[0].1.count, SAMPLES + 2);
+        assert_eq!(reasons[0].1.samples.len(), SAMPLES);
+        assert_eq!(reasons[0].1.samples.front().unwrap(), "line 2");
+        assert_eq!(reasons[0].1.samples.back().unwrap(), &format!("line {}", SAMPLES + 1));
+        assert_eq!(reasons[1].1.count, 1);
+        assert_eq!(reasons[1].1.samples[0].len(), SAMPLE_LENGTH);
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+/// A line of Docker's json-file log driver
+#[derive(Deserialize)]
+struct DockerLine {
+    log: String,
+    stream: String,
+    time: String,
+}
+
+/// A part of a line as the runtime wrote it
+struct Chunk {
+    content: String,
+    /// `stdout` or `stderr`
+    stream: String,
+    time: DateTime<Utc>,
+    last: bool,
+}
+
+/// Takes the wrapper of the container runtime off the lines of node log files and
+/// `docker logs`, joining long lines it split. Docker ends the last chunk of a line
+/// with a newline, CRI tags the chunks `P` and the last one `F`.
+#[derive(Default)]
+pub struct Unwrapper {
+    /// The line read so far per stream with the time of its first chunk, the chunks of
+    /// stdout and stderr come interleaved
+    partial: HashMap<String, (String, DateTime<Utc>)>,
+}
+
+impl Unwrapper {
+    /// The line of the application with the time the runtime gave it, `None` while only
+    /// a part of it was read. Lines without a wrapper are returned as they are.
+    pub fn unwrap(&mut self, line: &str) -> Option<(String, Option<DateTime<Utc>>)> {
+        let chunk = match docker(line).or_else(|| cri(line)) {
+            Some(chunk) => { chunk }
+            None => { return Some((line.to_string(), None)); }
+        };
+        let (partial, _) = self.partial.entry(chunk.stream.clone()).or_insert_with(|| (String::new(), chunk.time));
+        partial.push_str(chunk.content.trim_end_matches('\n'));
+        match chunk.last {
+            true => { self.partial.remove(&chunk.stream).map(|(line, time)| (line, Some(time))) }
+            false => { None }
+        }
+    }
+
+    /// The lines only a part of was read, for a source that ended.
+    pub fn finish(&mut self) -> Vec<(String, Option<DateTime<Utc>>)> {
+        let mut lines: Vec<_> = self.partial.drain().map(|(_, (line, time))| (line, Some(time))).collect();
+        lines.sort_by_key(|(_, time)| *time);
+        lines
+    }
+}
+
+fn docker(line: &str) -> Option<Chunk> {
+    if !line.starts_with("{\"log\":") {
+        return None;
+    }
+    let docker: DockerLine = serde_json::from_str(line).ok()?;
+    Some(Chunk {
+        last: docker.log.ends_with('\n'),
+        time: DateTime::parse_from_rfc3339(&docker.time).ok()?.with_timezone(&Utc),
+        content: docker.log,
+        stream: docker.stream,
+    })
+}
+
+/// `2022-08-07T04:10:21.123456789Z stdout F message`
+fn cri(line: &str) -> Option<Chunk> {
+    let mut parts = line.splitn(4, ' ');
+    let time = DateTime::parse_from_rfc3339(parts.next()?).ok()?.with_timezone(&Utc);
+    let stream = parts.next()?;
+    if !matches!(stream, "stdout" | "stderr") {
+        return None;
+    }
+    let last = match parts.next()? {
+        "F" => { true }
+        "P" => { false }
+        _ => { return None; }
+    };
+    Some(Chunk { content: parts.next().unwrap_or_default().to_string(), stream: stream.to_string(), time, last })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use crate::envelope::Unwrapper;
+
+    #[test]
+    fn unwraps_docker_and_cri() {
+        let mut unwrapper = Unwrapper::default();
+        let mut line = |l: &str| unwrapper.unwrap(l).map(|(line, _)| line);
+        assert_eq!(line(r#"{"log":"{\"level\":\"info\"}\n","stream":"stdout","time":"2022-08-07T02:10:21.1Z"}"#).as_deref(), Some(r#"{"level":"info"}"#));
+        assert_eq!(line(r#"{"log":"{\"msg\":\"lo","stream":"stdout","time":"2022-08-07T02:10:21.1Z"}"#), None);
+        assert_eq!(line(r#"{"log":"ng\"}\n","stream":"stdout","time":"2022-08-07T02:10:21.1Z"}"#).as_deref(), Some(r#"{"msg":"long"}"#));
+        assert_eq!(line("2022-08-07T04:10:21.123456789+02:00 stderr P {\"msg\":").as_deref(), None);
+        assert_eq!(line("2022-08-07T04:10:21.123456789+02:00 stdout F out").as_deref(), Some("out"));
+        assert_eq!(line("2022-08-07T04:10:21.123456789+02:00 stderr F \"x y\"}").as_deref(), Some("{\"msg\":\"x y\"}"));
+        assert_eq!(line("2022-08-07T04:10:21Z stdout F ").as_deref(), Some(""));
+        assert_eq!(line("2022-08-07 plain text").as_deref(), Some("2022-08-07 plain text"));
+        assert_eq!(unwrapper.unwrap("2022-08-07T04:10:21Z stdout F plain"), Some(("plain".to_string(), Some(Utc.ymd(2022, 8, 7).and_hms(4, 10, 21)))));
+        assert_eq!(unwrapper.unwrap("plain"), Some(("plain".to_string(), None)));
+        assert_eq!(unwrapper.unwrap("2022-08-07T04:10:22Z stderr P cut"), None);
+        assert_eq!(unwrapper.unwrap(r#"{"log":"also cut","stream":"stdout","time":"2022-08-07T04:10:23Z"}"#), None);
+        assert_eq!(unwrapper.finish(), [
+            ("cut".to_string(), Some(Utc.ymd(2022, 8, 7).and_hms(4, 10, 22))),
+            ("also cut".to_string(), Some(Utc.ymd(2022, 8, 7).and_hms(4, 10, 23))),
+        ]);
+        assert!(unwrapper.finish().is_empty());
+    }
+}
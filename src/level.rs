@@ -1,37 +1,116 @@
+use std::collections::BTreeMap;
 use std::fmt;
 use std::str::FromStr;
+use std::sync::OnceLock;
 
 use serde::{Deserialize, Serialize};
 
-#[derive(PartialEq, Eq, PartialOrd, Copy, Clone, Deserialize, Serialize)]
+/// Ordered by severity, `UNPARSED` last as it has none
+#[allow(clippy::upper_case_acronyms)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone, Debug, Deserialize, Serialize)]
 pub enum Level {
+    TRACE,
+    DEBUG,
     INFO,
     WARN,
     ERROR,
-    DEBUG,
+    FATAL,
+    /// A line that couldn't be parsed, kept as it is
+    UNPARSED,
+}
+
+/// Names other loggers use, `levels` in `search.json` adds more
+const ALIASES: [(&str, Level); 12] = [
+    ("FINEST", Level::TRACE),
+    ("FINER", Level::TRACE),
+    ("FINE", Level::DEBUG),
+    ("INFORMATION", Level::INFO),
+    ("NOTICE", Level::INFO),
+    ("WARNING", Level::WARN),
+    ("ERR", Level::ERROR),
+    ("SEVERE", Level::ERROR),
+    ("DPANIC", Level::ERROR),
+    ("CRITICAL", Level::FATAL),
+    ("PANIC", Level::FATAL),
+    ("EMERGENCY", Level::FATAL),
+];
+
+static CONFIGURED: OnceLock<BTreeMap<String, Level>> = OnceLock::new();
+
+impl Level {
+    pub const ALL: [Level; 7] = [Level::TRACE, Level::DEBUG, Level::INFO, Level::WARN, Level::ERROR, Level::FATAL, Level::UNPARSED];
+
+    /// Adds aliases to the built-in ones, only the first call counts.
+    pub fn set_aliases(aliases: &BTreeMap<String, Level>) {
+        let _ = CONFIGURED.set(aliases.iter().map(|(name, level)| (name.to_uppercase(), *level)).collect());
+    }
+
+    /// pino and bunyan numbers, 10 is TRACE up to 60 for FATAL
+    pub fn from_number(n: u64) -> Level {
+        match n {
+            0..=10 => { Level::TRACE }
+            11..=20 => { Level::DEBUG }
+            21..=30 => { Level::INFO }
+            31..=40 => { Level::WARN }
+            41..=50 => { Level::ERROR }
+            _ => { Level::FATAL }
+        }
+    }
 }
 
 impl fmt::Display for Level {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
+            Level::TRACE => { write!(f, "TRACE") }
+            Level::DEBUG => { write!(f, "DEBUG") }
             Level::INFO => { write!(f, "INFO") }
             Level::WARN => { write!(f, "WARN") }
             Level::ERROR => { write!(f, "ERROR") }
-            Level::DEBUG => { write!(f, "DEBUG") }
+            Level::FATAL => { write!(f, "FATAL") }
+            Level::UNPARSED => { write!(f, "UNPARSED") }
         }
     }
 }
 
+/// A level name or alias in any case, or a number.
 impl FromStr for Level {
     type Err = ();
 
     fn from_str(input: &str) -> Result<Level, Self::Err> {
-        match input {
+        let input = input.trim();
+        if let Ok(n) = input.parse::<u64>() {
+            return Ok(Level::from_number(n));
+        }
+        let input = input.to_uppercase();
+        match input.as_str() {
+            "TRACE" => Ok(Level::TRACE),
+            "DEBUG" => Ok(Level::DEBUG),
             "INFO" => Ok(Level::INFO),
             "WARN" => Ok(Level::WARN),
-            "DEBUG" => Ok(Level::DEBUG),
             "ERROR" => Ok(Level::ERROR),
-            _ => Err(()),
+            "FATAL" => Ok(Level::FATAL),
+            _ => {
+                CONFIGURED.get().and_then(|c| c.get(&input)).copied()
+                    .or_else(|| ALIASES.iter().find(|(name, _)| *name == input).map(|(_, level)| *level))
+                    .ok_or(())
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::Level;
+
+    #[test]
+    fn parses_aliases_and_numbers() {
+        assert_eq!("warning".parse(), Ok(Level::WARN));
+        assert_eq!("Info".parse(), Ok(Level::INFO));
+        assert_eq!("CRITICAL".parse(), Ok(Level::FATAL));
+        assert_eq!("trace".parse(), Ok(Level::TRACE));
+        assert_eq!("50".parse(), Ok(Level::ERROR));
+        assert_eq!(" 30 ".parse(), Ok(Level::INFO));
+        assert_eq!("verbose".parse::<Level>(), Err(()));
+        assert!(Level::TRACE < Level::DEBUG && Level::ERROR < Level::FATAL);
+    }
+}
@@ -0,0 +1,86 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Splits a logfmt line, `ts=2022-08-07T02:10:22Z level=info msg="hello world" app=foo`,
+/// into its pairs, a key without `=` gets an empty value. `None` when the line doesn't
+/// look like logfmt, fewer than half of its words being `key=value`.
+pub fn parse(line: &str) -> Option<Vec<(String, String)>> {
+    let mut pairs = Vec::new();
+    let mut with_value = 0;
+    let mut chars = line.trim().chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+        let mut key = String::new();
+        while let Some(c) = chars.next_if(|c| *c != '=' && !c.is_whitespace()) {
+            if c == '"' {
+                return None;
+            }
+            key.push(c);
+        }
+        if key.is_empty() {
+            return None;
+        }
+        let value = match chars.next_if_eq(&'=') {
+            Some(_) => {
+                with_value += 1;
+                match chars.next_if_eq(&'"') {
+                    Some(_) => { quoted(&mut chars)? }
+                    None => {
+                        let mut value = String::new();
+                        while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                            value.push(c);
+                        }
+                        value
+                    }
+                }
+            }
+            None => { String::new() }
+        };
+        pairs.push((key, value));
+    }
+    match with_value * 2 > pairs.len() {
+        true => { Some(pairs) }
+        false => { None }
+    }
+}
+
+/// The rest of a quoted value after the opening quote.
+fn quoted(chars: &mut Peekable<Chars>) -> Option<String> {
+    let mut value = String::new();
+    loop {
+        match chars.next()? {
+            '"' => { return Some(value); }
+            '\\' => {
+                match chars.next()? {
+                    'n' => { value.push('\n') }
+                    't' => { value.push('\t') }
+                    c => { value.push(c) }
+                }
+            }
+            c => { value.push(c) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::logfmt::parse;
+
+    #[test]
+    fn parses_logfmt() {
+        let pairs = parse(r#"ts=2022-08-07T02:10:22Z level=info msg="hello \"world\"" app=foo retry"#).unwrap();
+        assert_eq!(pairs, vec![
+            ("ts".to_string(), "2022-08-07T02:10:22Z".to_string()),
+            ("level".to_string(), "info".to_string()),
+            ("msg".to_string(), "hello \"world\"".to_string()),
+            ("app".to_string(), "foo".to_string()),
+            ("retry".to_string(), "".to_string()),
+        ]);
+        assert!(parse("Starting app v1.2").is_none());
+        assert!(parse("Connecting to db host=x").is_none());
+        assert!(parse(r#"msg="unterminated"#).is_none());
+    }
+}
@@ -1,10 +1,10 @@
 extern crate core;
 
-use std::{cmp::max, collections::HashSet, error::Error, fs, io, sync::Arc, sync::atomic::AtomicBool, sync::atomic::Ordering as OtherOrdering, sync::mpsc, time::Duration};
+use std::{cmp::max, collections::BTreeMap, collections::BTreeSet, collections::HashSet, error::Error, fs, io, sync::atomic::Ordering as OtherOrdering, sync::mpsc, time::Duration};
 
 use bytesize::ByteSize;
 use crossterm::{event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode}, execute, terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen}};
-use crossterm::event::{KeyModifiers, MouseEventKind};
+use crossterm::event::{KeyModifiers, MouseButton, MouseEventKind};
 use num_format::{Locale, ToFormattedString};
 use serde::{Deserialize, Serialize};
 use tui::{
@@ -17,28 +17,69 @@ use tui::{
 };
 use tui::layout::{Alignment, Rect};
 use tui::style::Modifier;
-use tui::widgets::{List, ListItem, ListState};
+use tui::widgets::{Clear, List, ListItem, ListState};
 
 use search_thread::command_message::CommandMessage;
 use search_thread::result_message::ResultMessage;
 
 use crate::app::App;
+use crate::args::Args;
+use crate::config::Config;
 use crate::level::Level;
 use crate::message::Message;
-use crate::Mode::{Search, SelectPods, SelectTopics};
+use crate::Mode::{Diagnostics, Search, SelectContext, SelectNamespace, SelectPods, SelectTopics, Sources};
 use crate::parse_send::parse_and_send;
-use crate::pod::populate_pods::{populate_pods, populate_topics};
-use crate::spawn_reader_thread::{clean_up_threads, spawn_reader_thread, spawn_reader_thread_kafka};
+use crate::pod::populate_pods::{populate_contexts, populate_namespaces, populate_pods, populate_topics};
+use crate::source::command::CommandSource;
+use crate::source::pod::PodSource;
+use crate::source::watch::{WatchSource, WatchTarget};
+use crate::pod::cluster::{KubeTarget, stable_selector};
+use crate::source::{LogSource, sources_from_args, SourceState, SourceStatus};
+use crate::source::stdin::take_piped_stdin;
+use crate::stack::StackView;
 
 mod pod;
 mod search_thread;
 mod app;
 mod message;
 mod parse_send;
-mod spawn_reader_thread;
+mod source;
 mod level;
+mod tail;
+mod args;
+mod config;
+mod mapping;
+mod diagnostics;
+mod logfmt;
+mod pattern;
+mod multiline;
+mod timestamp;
+mod detect;
+mod envelope;
+mod stack;
+
+/// CTRL and these keys show or hide a level
+const LEVEL_KEYS: [(char, Level); 7] = [
+    ('y', Level::TRACE),
+    ('q', Level::DEBUG),
+    ('w', Level::INFO),
+    ('e', Level::WARN),
+    ('r', Level::ERROR),
+    ('u', Level::FATAL),
+    ('t', Level::UNPARSED),
+];
 
 fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse(std::env::args().skip(1))?;
+    let config = Config::load()?;
+    if let Some(profile) = &args.options.profile {
+        config.profile(profile)?;
+    }
+    if let Some(parser) = &args.options.parser {
+        config.parser(parser, args.options.profile.as_deref())?;
+    }
+    let stdin = take_piped_stdin()?;
+    let sources = sources_from_args(&args, &config, stdin)?;
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen,EnableMouseCapture)?;
@@ -48,9 +89,13 @@ fn main() -> Result<(), Box<dyn Error>> {
     //Command channel for searching etc
     let (tx, rx) = mpsc::channel();
     let (tx_result, rx_result) = mpsc::channel();
-    let app = App::default(tx, rx_result);
+    let mut app = App::default(tx, rx_result);
+    app.target = args.target.clone();
+    app.options = args.options.clone();
 
-    search_thread::search_thread(rx, tx_result);
+    search_thread::search_thread(rx, tx_result, config.memory);
+    sources.into_iter().for_each(|s| app.add_source(s));
+    app.config = config;
 
     let res = run_app(&mut terminal, app);
     // restore terminal
@@ -93,6 +138,10 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<(
                 ResultMessage::Skip(s) => {
                     app.skip = s
                 }
+                ResultMessage::Evicted(evicted, oldest) => {
+                    app.evicted = evicted;
+                    app.oldest = oldest;
+                }
             }
         }
 
@@ -102,49 +151,79 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<(
         changed = true;
         match event::read()? {
             Event::Key(key) => {
+                app.error = None;
                 match app.mode {
+                    SelectContext | SelectNamespace => {
+                        match key.code {
+                            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                app.stop_sources();
+                                app.tx.send(CommandMessage::Exit).unwrap();
+                                return Ok(());
+                            }
+                            KeyCode::Down => app.pods.next(),
+                            KeyCode::Up => app.pods.previous(),
+                            KeyCode::Esc => app.mode = Search,
+                            KeyCode::Enter => {
+                                let chosen = app.pods.state.selected().and_then(|i| app.pods.items.get(i)).map(|p| p.name.clone());
+                                if let SelectContext = app.mode {
+                                    app.target = KubeTarget { context: chosen, namespace: None };
+                                    app.mode = SelectNamespace;
+                                    populate_namespaces(&mut app);
+                                } else {
+                                    app.target.namespace = chosen;
+                                    app.mode = SelectPods;
+                                    populate_pods(&mut app);
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
                     SelectPods | SelectTopics => {
                         match key.code {
                             KeyCode::Char(c) => {
                                 if key.modifiers.contains(KeyModifiers::CONTROL) && c == 'c' {
-                                    clean_up_threads(&mut app);
+                                    app.stop_sources();
                                     app.tx.send(CommandMessage::Exit).unwrap();
                                     return Ok(());
                                 }
                                 if key.modifiers.contains(KeyModifiers::CONTROL) && c == 'a' {
                                     app.pods.select_all();
                                 }
+                                if key.modifiers.contains(KeyModifiers::CONTROL) && c == 'f' {
+                                    if let SelectPods = app.mode {
+                                        let selectors: BTreeSet<String> = app.pods.selected.iter()
+                                            .filter_map(|pod_index| stable_selector(&app.pods.items[*pod_index].labels))
+                                            .collect();
+                                        if selectors.is_empty() {
+                                            app.error = Some("the selected pods have no labels that stay across rollouts".to_string());
+                                            continue;
+                                        }
+                                        let target = app.target.clone();
+                                        selectors.into_iter().for_each(|s| app.add_picked(Box::new(WatchSource::new(WatchTarget::Selector(s), &target))));
+                                        app.mode = Search;
+                                        continue;
+                                    }
+                                }
                                 if key.modifiers.contains(KeyModifiers::CONTROL) && c == 'p' || key.modifiers.contains(KeyModifiers::CONTROL) && c == 'k' {
                                     let selected_pods: Vec<_> = app.pods.selected.iter().map(|pod_index| { &app.pods.items[*pod_index] }).collect();
 
-                                    app.stops.clear();
-                                    let stops = match app.mode {
+                                    let sources: Vec<Box<dyn LogSource>> = match app.mode {
                                         SelectPods => {
-                                            let stops: Vec<_> = selected_pods.iter().map(|pod| {
-                                                let name = pod.name.clone();
-                                                let sender = app.tx.clone();
-                                                let please_stop = Arc::new(AtomicBool::new(false));
-                                                let should_i_stop = please_stop.clone();
-                                                return (please_stop, spawn_reader_thread(name, sender, should_i_stop));
-                                            }).collect();
-                                            stops
+                                            selected_pods.iter().flat_map(|pod| {
+                                                let current = Box::new(PodSource::new(&pod.name, pod.container.as_deref(), &app.target)) as Box<dyn LogSource>;
+                                                match pod.crashloop {
+                                                    true => { vec![Box::new(PodSource::previous(&pod.name, pod.container.as_deref(), &app.target)) as Box<dyn LogSource>, current] }
+                                                    false => { vec![current] }
+                                                }
+                                            }).collect()
                                         }
                                         SelectTopics => {
                                             let pods: Vec<_> = selected_pods.iter().map(|pod| { pod.name.clone() }).collect();
-                                            let name = pods.join(" ");
-                                            let sender = app.tx.clone();
-                                            let please_stop = Arc::new(AtomicBool::new(false));
-                                            let should_i_stop = please_stop.clone();
-                                            let vec: Vec<_> = vec![(please_stop, spawn_reader_thread_kafka(name, sender, should_i_stop))];
-                                            vec
+                                            vec![Box::new(CommandSource::kafka(&pods.join(" ")))]
                                         }
                                         _ => { panic!("Not possible") }
                                     };
-
-
-                                    let (x, y): (Vec<_>, Vec<_>) = stops.into_iter().map(|(a, b)| (a, b)).unzip();
-                                    app.stops = x;
-                                    app.handles = y;
+                                    sources.into_iter().for_each(|s| app.add_picked(s));
                                     app.mode = Search;
                                     continue;
                                 }
@@ -155,6 +234,78 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<(
                             _ => {}
                         }
                     }
+                    Sources => {
+                        let len = app.sources.len();
+                        match key.code {
+                            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                app.stop_sources();
+                                app.tx.send(CommandMessage::Exit).unwrap();
+                                return Ok(());
+                            }
+                            KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                app.mode = Search;
+                            }
+                            KeyCode::Esc => {
+                                app.mode = Search;
+                            }
+                            KeyCode::Down if len > 0 => {
+                                let i = app.sources_state.selected().map(|i| (i + 1) % len).unwrap_or(0);
+                                app.sources_state.select(Some(i));
+                            }
+                            KeyCode::Up if len > 0 => {
+                                let i = app.sources_state.selected().map(|i| (i + len - 1) % len).unwrap_or(0);
+                                app.sources_state.select(Some(i));
+                            }
+                            KeyCode::Enter => {
+                                if let Some(source) = app.selected_source() {
+                                    match source.status() {
+                                        SourceStatus::Paused => { source.resume() }
+                                        _ => { source.pause() }
+                                    }
+                                }
+                            }
+                            KeyCode::Char('r') => {
+                                let sender = app.tx.clone();
+                                if let Some(source) = app.selected_source().filter(|s| s.restartable()) {
+                                    source.restart(sender);
+                                }
+                            }
+                            KeyCode::Char('s') => {
+                                if let Some(source) = app.selected_source() {
+                                    source.stop();
+                                }
+                            }
+                            KeyCode::Char('p') => {
+                                app.next_profile();
+                            }
+                            KeyCode::Char('f') => {
+                                app.next_parser();
+                            }
+                            _ => {}
+                        }
+                    }
+                    Diagnostics => {
+                        match key.code {
+                            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                app.stop_sources();
+                                app.tx.send(CommandMessage::Exit).unwrap();
+                                return Ok(());
+                            }
+                            KeyCode::Char('x') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                app.mode = Search;
+                            }
+                            KeyCode::Esc => {
+                                app.mode = Search;
+                            }
+                            KeyCode::Down => {
+                                app.diagnostics_scroll = app.diagnostics_scroll.saturating_add(1);
+                            }
+                            KeyCode::Up => {
+                                app.diagnostics_scroll = app.diagnostics_scroll.saturating_sub(1);
+                            }
+                            _ => {}
+                        }
+                    }
                     Search => {
                         match key.code {
                             KeyCode::Up => {
@@ -185,75 +336,80 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<(
                                     continue;
                                 }
                                 if key.modifiers.contains(KeyModifiers::CONTROL) && c == 'c' {
-                                    clean_up_threads(&mut app);
+                                    app.stop_sources();
                                     app.tx.send(CommandMessage::Exit).unwrap();
                                     return Ok(());
                                 }
-                                if key.modifiers.contains(KeyModifiers::CONTROL) && c == 'q' {
-                                    app.show_debug = !app.show_debug;
-                                    app.tx.send(CommandMessage::ToggleDebug()).unwrap();
+                                if let Some((_, level)) = LEVEL_KEYS.iter().find(|(k, _)| key.modifiers.contains(KeyModifiers::CONTROL) && *k == c) {
+                                    if !app.hidden_levels.remove(level) {
+                                        app.hidden_levels.insert(*level);
+                                    }
+                                    app.tx.send(CommandMessage::ToggleLevel(*level)).unwrap();
                                     continue;
                                 }
-                                if key.modifiers.contains(KeyModifiers::CONTROL) && c == 'w' {
-                                    app.show_info = !app.show_info;
-                                    app.tx.send(CommandMessage::ToggleInfo()).unwrap();
+                                if key.modifiers.contains(KeyModifiers::CONTROL) && c == 'l' {
+                                    app.wrap = !app.wrap;
                                     continue;
                                 }
-                                if key.modifiers.contains(KeyModifiers::CONTROL) && c == 'e' {
-                                    app.show_warn = !app.show_warn;
-                                    app.tx.send(CommandMessage::ToggleWarn()).unwrap();
+                                if key.modifiers.contains(KeyModifiers::CONTROL) && c == 'f' {
+                                    app.show_fields = !app.show_fields;
                                     continue;
                                 }
-                                if key.modifiers.contains(KeyModifiers::CONTROL) && c == 'r' {
-                                    app.show_error = !app.show_error;
-                                    app.tx.send(CommandMessage::ToggleError()).unwrap();
+                                if key.modifiers.contains(KeyModifiers::CONTROL) && c == 'o' {
+                                    app.stacks.toggle_all();
                                     continue;
                                 }
-                                if key.modifiers.contains(KeyModifiers::CONTROL) && c == 'l' {
-                                    app.wrap = !app.wrap;
+                                if key.modifiers.contains(KeyModifiers::CONTROL) && c == 'g' {
+                                    app.stacks.hide_framework = !app.stacks.hide_framework;
+                                    continue;
+                                }
+                                if key.modifiers.contains(KeyModifiers::CONTROL) && c == 'x' {
+                                    app.mode = Diagnostics;
+                                    app.diagnostics_scroll = 0;
+                                    continue;
+                                }
+                                if key.modifiers.contains(KeyModifiers::CONTROL) && c == 's' {
+                                    app.mode = Sources;
+                                    if app.sources_state.selected().is_none() && !app.sources.is_empty() {
+                                        app.sources_state.select(Some(0));
+                                    }
                                     continue;
                                 }
                                 if key.modifiers.contains(KeyModifiers::CONTROL) && c == 'k' {
                                     app.mode = SelectTopics;
-                                    clean_up_threads(&mut app);
-
-                                    app.tx.send(CommandMessage::Clear).unwrap();
+                                    if !app.stop_picked() {
+                                        app.tx.send(CommandMessage::Clear).unwrap();
+                                    }
                                     populate_topics(&mut app);
                                     continue;
                                 }
                                 if key.modifiers.contains(KeyModifiers::CONTROL) && c == 'p' {
-                                    app.mode = SelectPods;
-                                    clean_up_threads(&mut app);
-
-                                    app.tx.send(CommandMessage::Clear).unwrap();
-                                    populate_pods(&mut app);
+                                    app.mode = SelectContext;
+                                    if !app.stop_picked() {
+                                        app.tx.send(CommandMessage::Clear).unwrap();
+                                    }
+                                    populate_contexts(&mut app);
                                     continue;
                                 }
                                 app.input.insert(app.input_index, c);
                                 app.input_index += 1;
                                 filter(&mut app);
                             }
-                            KeyCode::Backspace => {
-                                if app.input_index > 0 {
-                                    app.input_index -= 1;
-                                    app.input.remove(app.input_index);
-                                    filter(&mut app);
-                                }
+                            KeyCode::Backspace if app.input_index > 0 => {
+                                app.input_index -= 1;
+                                app.input.remove(app.input_index);
+                                filter(&mut app);
                             }
-                            KeyCode::Left => {
-                                if app.input_index > 0 {
-                                    let (x, y) = terminal.get_cursor().unwrap();
-                                    terminal.set_cursor(x - 1, y).ok();
-                                    app.input_index -= 1
-                                }
+                            KeyCode::Left if app.input_index > 0 => {
+                                let (x, y) = terminal.get_cursor().unwrap();
+                                terminal.set_cursor(x - 1, y).ok();
+                                app.input_index -= 1
                             }
 
-                            KeyCode::Right => {
-                                if app.input_index < app.input.len() {
-                                    let (x, y) = terminal.get_cursor().unwrap();
-                                    terminal.set_cursor(x + 1, y).ok();
-                                    app.input_index += 1
-                                }
+                            KeyCode::Right if app.input_index < app.input.len() => {
+                                let (x, y) = terminal.get_cursor().unwrap();
+                                terminal.set_cursor(x + 1, y).ok();
+                                app.input_index += 1
                             }
                             _ => {}
                         }
@@ -262,6 +418,11 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<(
             }
             Event::Mouse(mouse) => {
                 match mouse.kind {
+                    MouseEventKind::Down(MouseButton::Left) if matches!(app.mode, Search) => {
+                        if let Some((_, _, key)) = app.message_rows.iter().find(|(start, end, _)| (*start..*end).contains(&mouse.row)) {
+                            app.stacks.toggle(*key);
+                        }
+                    }
                     MouseEventKind::Down(_) => {}
                     MouseEventKind::Up(_) => {}
                     MouseEventKind::Drag(_) => {}
@@ -295,19 +456,31 @@ fn filter(app: &mut App) {
     let query: String = app.input.iter().collect();
     let x: Vec<_> = query.split(" ").collect();
     let neg_query: Vec<_> = x.iter().filter(|v| v.starts_with("!") && v.len() > 1).map(|v| v.strip_prefix("!").unwrap().to_string()).collect();
-    let pos_query: Vec<_> = x.iter().filter(|&v| !v.starts_with("!")).map(|v| *v).collect();
+    // `@key=value` or just `@key` for messages with that field
+    let field_query: Vec<_> = x.iter().filter(|v| v.starts_with("@") && v.len() > 1).map(|v| {
+        match v[1..].split_once('=') {
+            Some((k, v)) => { (k.to_string(), v.to_string()) }
+            None => { (v[1..].to_string(), ".*".to_string()) }
+        }
+    }).collect();
+    let pos_query: Vec<_> = x.iter().filter(|&v| !v.starts_with("!") && !v.starts_with("@")).copied().collect();
     let pos_query = pos_query.join(" ");
     app.tx.send(CommandMessage::FilterRegex(pos_query)).unwrap();
     app.tx.send(CommandMessage::FilterNotRegexes(neg_query)).unwrap();
+    app.tx.send(CommandMessage::FilterFields(field_query)).unwrap();
 }
 
 enum Mode {
+    SelectContext,
+    SelectNamespace,
     SelectPods,
     SelectTopics,
     Search,
+    Sources,
+    Diagnostics,
 }
 
-fn ui<B: Backend>(f: &mut Frame<B>, mut app: &mut App) {
+fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .margin(0)
@@ -325,14 +498,20 @@ fn ui<B: Backend>(f: &mut Frame<B>, mut app: &mut App) {
         app.tx.send(CommandMessage::SetResultSize(chunks[0].height.into())).unwrap();
     }
     match app.mode {
-        SelectPods | SelectTopics => {
+        SelectContext | SelectNamespace | SelectPods | SelectTopics => {
+            let title = match app.mode {
+                SelectContext => { "Select context".to_string() }
+                SelectNamespace => { format!("Select namespace in {}", app.target.context.as_deref().unwrap_or("current context")) }
+                SelectPods => { format!("Select pods in {}", app.target.namespace.as_deref().unwrap_or("current namespace")) }
+                _ => { "Select".to_string() }
+            };
             let items: Vec<ListItem> = app
                 .pods
                 .items
                 .iter()
                 .enumerate()
                 .map(|i| {
-                    ListItem::new(Spans::from(i.1.name.clone())).style(Style::default().fg(match app.pods.selected().contains(&i.0) {
+                    ListItem::new(Spans::from(i.1.label())).style(Style::default().fg(match app.pods.selected().contains(&i.0) {
                         true => { Color::Red }
                         false => { Color::White }
                     }))
@@ -340,7 +519,10 @@ fn ui<B: Backend>(f: &mut Frame<B>, mut app: &mut App) {
                 .collect();
 
             let items = List::new(items)
-                .block(Block::default().borders(Borders::NONE).title("Select"))
+                .block(Block::default().borders(Borders::NONE).title(Spans::from(vec![
+                    Span::raw(title),
+                    Span::styled(app.error.as_ref().map(|e| format!(" ── {}", e)).unwrap_or_default(), Style::default().fg(Color::Red)),
+                ])))
                 .highlight_style(
                     Style::default()
                         .bg(Color::LightGreen)
@@ -353,6 +535,105 @@ fn ui<B: Backend>(f: &mut Frame<B>, mut app: &mut App) {
         Search => {
             render_search(f, app, chunks)
         }
+        Sources => {
+            render_sources(f, app, chunks)
+        }
+        Diagnostics => {
+            let area = chunks[0];
+            render_search(f, app, chunks);
+            render_diagnostics(f, app, area)
+        }
+    }
+}
+
+/// Lines each source couldn't parse, by reason with the last few of them, over the search view.
+fn render_diagnostics<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
+    let area = Rect {
+        x: area.x + area.width / 10,
+        y: area.y + area.height / 10,
+        width: area.width - area.width / 5,
+        height: area.height - area.height / 5,
+    };
+    let mut lines = Vec::new();
+    for s in &app.sources {
+        let mut sources = vec![(s.kind().to_string(), s.name().to_string(), s.state().clone())];
+        s.children().into_iter().for_each(|c| sources.push((c.kind, c.name, c.state)));
+        for (kind, name, state) in sources {
+            let diagnostics = state.diagnostics();
+            lines.push(Spans::from(vec![
+                Span::styled(format!("{:<6} ", kind), Style::default().fg(Color::Cyan)),
+                Span::raw(format!("{} ", name)),
+                Span::styled(format!("── {} of {} lines rejected ── profile {}", diagnostics.total(), state.lines(), state.mapping().name), Style::default().fg(match diagnostics.total() {
+                    0 => { Color::Green }
+                    _ => { Color::Red }
+                })),
+            ]));
+            for (reason, rejected) in diagnostics.reasons() {
+                lines.push(Spans::from(Span::raw(format!("  {} ── {}", reason, rejected.count))));
+                rejected.samples.iter().for_each(|l| lines.push(Spans::from(Span::styled(format!("    {}", l), Style::default().fg(Color::Gray)))));
+            }
+        }
+    }
+    if lines.is_empty() {
+        lines.push(Spans::from("No sources"));
+    }
+    let diagnostics = Paragraph::new(Text::from(lines))
+        .block(Block::default().borders(Borders::ALL).title("Diagnostics ── Up/Down scroll, Esc back"))
+        .scroll((app.diagnostics_scroll, 0));
+    f.render_widget(Clear, area);
+    f.render_widget(diagnostics, area);
+}
+
+fn render_sources<B: Backend>(f: &mut Frame<B>, app: &mut App, chunks: Vec<Rect>) {
+    let items: Vec<ListItem> = app.sources.iter()
+        .map(|s| {
+            let mut lines = vec![source_line("", &s.status(), s.kind(), s.name(), s.state())];
+            if !s.restartable() && matches!(s.status(), SourceStatus::Stopped | SourceStatus::Ended) {
+                lines[0].0.push(Span::styled(" ── can't be restarted", Style::default().fg(Color::Gray)));
+            }
+            s.children().iter().for_each(|c| lines.push(source_line("  ", &c.state.status(), &c.kind, &c.name, &c.state)));
+            ListItem::new(Text::from(lines))
+        })
+        .collect();
+    let items = List::new(items)
+        .block(Block::default().borders(Borders::NONE).title("Sources"))
+        .highlight_style(
+            Style::default()
+                .bg(Color::LightGreen)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("");
+    f.render_stateful_widget(items, chunks[0], &mut app.sources_state);
+
+    let help = Paragraph::new(Span::styled("Enter pause/resume, r restart, s stop, p profile, f parser, Esc back", Style::default().fg(Color::Cyan)))
+        .alignment(Alignment::Right);
+    f.render_widget(help, chunks[1]);
+}
+
+fn source_line<'a>(indent: &'a str, status: &SourceStatus, kind: &str, name: &str, state: &SourceState) -> Spans<'a> {
+    Spans::from(vec![
+        Span::raw(indent),
+        Span::styled(format!("{:<12} ", status.to_string()), Style::default().fg(match status {
+            SourceStatus::Running => { Color::Green }
+            SourceStatus::Paused | SourceStatus::Starting | SourceStatus::Reconnecting => { Color::Yellow }
+            SourceStatus::Failed(_) => { Color::Red }
+            SourceStatus::Stopped | SourceStatus::Ended => { Color::Gray }
+        })),
+        Span::styled(format!("{:<6} ", kind), Style::default().fg(Color::Cyan)),
+        Span::raw(format!("{} ", name)),
+        Span::styled(format!("── lines {} ── {} ── {} ── profile {}", state.lines().to_formatted_string(&Locale::fr), ByteSize::b(state.bytes()), parser_label(state), state.mapping().name), Style::default().fg(Color::Cyan)),
+        Span::styled(match state.progress() {
+            Some(p) => { format!(" ── file {}/{} {}%", p.file, p.files, p.read * 100 / p.total.max(1)) }
+            None => { String::new() }
+        }, Style::default().fg(Color::Yellow)),
+    ])
+}
+
+/// The parser of a source, `auto` marks the one it picked.
+fn parser_label(state: &SourceState) -> String {
+    match state.detected() {
+        true => { format!("auto: {}", state.parser()) }
+        false => { state.parser().to_string() }
     }
 }
 
@@ -364,20 +645,20 @@ fn render_search<B: Backend>(f: &mut Frame<B>, app: &mut App, chunks: Vec<Rect>)
     for x in &app.messages {
         m.push(x.clone());
         if app.wrap {
-            line_shifts += x.value.chars().filter(|c| *c == '\n').count() + 1;
+            line_shifts += app.stacks.text(x).chars().filter(|c| *c == '\n').count() + 1;
             if line_shifts > screen_height as usize + app.last_message_height {
                 break;
             }
         }
     }
-    if m.len() > 0 {
+    if !m.is_empty() {
         let mut mm = Vec::new();
         let option: Option<&Message> = m.last();
         mm.push(option.unwrap().clone());
-        app.last_message_height = get_concatinated(&map_from_messages_to_text(&chunks, &mm, app.wrap)).height()
+        app.last_message_height = get_concatinated(&map_from_messages_to_text(&chunks, &mm, app.wrap, app.show_fields, &app.stacks)).height()
     }
 
-    let messages = map_from_messages_to_text(&chunks, &m, app.wrap);
+    let messages = map_from_messages_to_text(&chunks, &m, app.wrap, app.show_fields, &app.stacks);
     let con_messages = get_concatinated(&messages);
 
     if app.just_skipped_bottom {
@@ -385,63 +666,72 @@ fn render_search<B: Backend>(f: &mut Frame<B>, app: &mut App, chunks: Vec<Rect>)
         app.just_skipped_bottom = false
     }
 
-    if app.just_skipped {
-        if app.dropped_bottom_messages >= app.last_message_height {
-            app.dropped_bottom_messages -= app.last_message_height;
-        }
+    if app.just_skipped && app.dropped_bottom_messages >= app.last_message_height {
+        app.dropped_bottom_messages -= app.last_message_height;
     }
     let messages_height = con_messages.height();
     let top_skip: usize = max(messages_height as i32 - screen_height as i32 - app.dropped_bottom_messages as i32, 0).try_into().unwrap();
-    if messages_height >= screen_height as usize {
-        if app.dropped_bottom_messages >= app.last_message_height {
-            app.skip += 1;
-            app.tx.send(CommandMessage::SetSkip(app.skip)).unwrap();
-            app.just_skipped = true;
-            return render_search(f, app, chunks);
-        }
+    if messages_height >= screen_height as usize && app.dropped_bottom_messages >= app.last_message_height {
+        app.skip += 1;
+        app.tx.send(CommandMessage::SetSkip(app.skip)).unwrap();
+        app.just_skipped = true;
+        return render_search(f, app, chunks);
     }
     app.just_skipped = false;
 
+    // The rows every message takes on the screen, for clicks on them
+    let mut row = 0;
+    app.message_rows = messages.iter().zip(m.iter().rev()).filter_map(|(text, message)| {
+        let (start, end) = (row, row + text.height());
+        row = end;
+        match end > top_skip && start < top_skip + screen_height as usize {
+            true => { Some((start.saturating_sub(top_skip) as u16 + chunks[0].y, (end - top_skip) as u16 + chunks[0].y, stack::key(message))) }
+            false => { None }
+        }
+    }).collect();
+
     let x: Vec<_> = con_messages.lines.into_iter().skip(top_skip).take(screen_height as usize).collect();
     let messages = Paragraph::new(Text::from(x)).block(Block::default().borders(Borders::NONE));
 
-    let (msg, style) = (
-        vec![
-            Span::styled("┌─ ", Style::default().fg(Color::Cyan)),
-            Span::styled(format!("{:.2?}── ", app.elapsed), Style::default().fg(Color::Cyan)),
-            Span::styled(match app.skip {
-                0 => { "Following" }
-                _ => { "Enter to follow" }
-            }, Style::default().fg(Color::Cyan)),
-            Span::styled(format!(" ── total lines {} ── ", app.length.to_formatted_string(&Locale::fr)), Style::default().fg(Color::Cyan)),
-            Span::styled("", Style::default().fg(Color::Cyan)),
-            Span::styled(format!("{}", ByteSize::b(app.size)), Style::default().fg(Color::Cyan)),
-            Span::styled(format!(" ── {}", "CTRL-q "), Style::default().fg(Color::Cyan)),
-            Span::styled(format!("{}", "DEBUG"), Style::default().fg(match app.show_debug {
-                true => { Color::Blue }
-                false => { Color::Cyan }
-            })),
-            Span::styled(format!("{}", ", CTRL-w "), Style::default().fg(Color::Cyan)),
-            Span::styled(format!("{}", "INFO"), Style::default().fg(match app.show_info {
-                true => { Color::Green }
-                false => { Color::Cyan }
-            })),
-            Span::styled(format!("{}", ", CTRL-e "), Style::default().fg(Color::Cyan)),
-            Span::styled(format!("{}", "WARN"), Style::default().fg(match app.show_warn {
-                true => { Color::Magenta }
-                false => { Color::Cyan }
-            })),
-            Span::styled(format!("{}", ", CTRL-r "), Style::default().fg(Color::Cyan)),
-            Span::styled(format!("{}", "ERROR"), Style::default().fg(match app.show_error {
-                true => { Color::Red }
-                false => { Color::Cyan }
-            })),
-            Span::styled(format!("{}", ", CTRL-l wrap"), Style::default().fg(Color::Cyan)),
-            Span::styled(format!("{}", ", CTRL-p pods"), Style::default().fg(Color::Cyan)),
-        ],
-        Style::default());
-    let mut text = Text::from(Spans::from(msg));
-    text.patch_style(style);
+    let mut msg = vec![
+        Span::styled("┌─ ", Style::default().fg(Color::Cyan)),
+        Span::styled(format!("{:.2?}── ", app.elapsed), Style::default().fg(Color::Cyan)),
+        Span::styled(match app.skip {
+            0 => { "Following" }
+            _ => { "Enter to follow" }
+        }, Style::default().fg(Color::Cyan)),
+        Span::styled(format!(" ── total lines {} ── ", app.length.to_formatted_string(&Locale::fr)), Style::default().fg(Color::Cyan)),
+        Span::styled("", Style::default().fg(Color::Cyan)),
+        Span::styled(format!("{}", ByteSize::b(app.size)), Style::default().fg(Color::Cyan)),
+        Span::styled(" ──", Style::default().fg(Color::Cyan)),
+    ];
+    if app.evicted > 0 {
+        msg.push(Span::styled(format!(" evicted {} ── kept since {} ──",
+                                      app.evicted.to_formatted_string(&Locale::fr),
+                                      app.oldest.map(|t| t.format("%+").to_string()).unwrap_or_default()), Style::default().fg(Color::Yellow)));
+    }
+    for level in Level::ALL {
+        let (key, _) = LEVEL_KEYS.iter().find(|(_, l)| *l == level).unwrap();
+        msg.push(Span::styled(format!(" CTRL-{} ", key), Style::default().fg(Color::Cyan)));
+        msg.push(Span::styled(level.to_string(), Style::default().fg(match app.hidden_levels.contains(&level) {
+            true => { Color::Cyan }
+            false => { level_color(level) }
+        })));
+        msg.push(Span::styled(",", Style::default().fg(Color::Cyan)));
+    }
+    msg.extend([
+        Span::styled(" CTRL-l wrap".to_string(), Style::default().fg(Color::Cyan)),
+        Span::styled(", CTRL-f fields".to_string(), Style::default().fg(Color::Cyan)),
+        Span::styled(", CTRL-o traces".to_string(), Style::default().fg(Color::Cyan)),
+        Span::styled(match app.stacks.hide_framework {
+            true => { ", CTRL-g show framework" }
+            false => { ", CTRL-g hide framework" }
+        }.to_string(), Style::default().fg(Color::Cyan)),
+        Span::styled(", CTRL-p pods".to_string(), Style::default().fg(Color::Cyan)),
+        Span::styled(", CTRL-s sources".to_string(), Style::default().fg(Color::Cyan)),
+        Span::styled(", CTRL-x diagnostics".to_string(), Style::default().fg(Color::Cyan)),
+    ]);
+    let text = Text::from(Spans::from(msg));
     let help_message = Paragraph::new(text).alignment(Alignment::Right);
 
     let s: String = app.input.iter().collect();
@@ -459,39 +749,62 @@ fn render_search<B: Backend>(f: &mut Frame<B>, app: &mut App, chunks: Vec<Rect>)
     );
 }
 
-fn get_concatinated<'a>(messages: &'a Vec<Text>) -> Text<'a> {
+fn level_color(level: Level) -> Color {
+    match level {
+        Level::TRACE => { Color::LightBlue }
+        Level::DEBUG => { Color::Blue }
+        Level::INFO => { Color::Green }
+        Level::WARN => { Color::Magenta }
+        Level::ERROR => { Color::Red }
+        Level::FATAL => { Color::LightRed }
+        Level::UNPARSED => { Color::Gray }
+    }
+}
+
+/// `namespace/system[container]`, leaving out what isn't known.
+fn system_label(m: &Message) -> String {
+    let mut label = match m.namespace.is_empty() {
+        true => { m.system.clone() }
+        false => { format!("{}/{}", m.namespace, m.system) }
+    };
+    if !m.container.is_empty() {
+        label.push_str(&format!("[{}]", m.container));
+    }
+    label
+}
+
+fn get_concatinated<'a>(messages: &'a [Text]) -> Text<'a> {
     messages.iter().fold(Text::raw(""), |mut sum, val| {
         sum.extend(val.clone());
         sum
     })
 }
 
-fn map_from_messages_to_text<'b>(chunks: &Vec<Rect>, messages: &'b Vec<Message>, wrap: bool) -> Vec<Text<'b>> {
+fn map_from_messages_to_text<'b>(chunks: &[Rect], messages: &'b [Message], wrap: bool, show_fields: bool, stacks: &StackView) -> Vec<Text<'b>> {
     let messages: Vec<_> = messages.iter()
         .map(|m| {
+            let value = stacks.text(m);
             let mut content = vec![
-                Span::styled(format!("{} ", format!("{}", m.timestamp.format("%+"))), Style::default().fg(Color::Cyan)),
-                Span::styled(format!("{} ", m.system), Style::default().fg(Color::Yellow)),
-                Span::styled(format!("{} ", m.level), Style::default().fg(match m.level {
-                    Level::INFO => { Color::Green }
-                    Level::WARN => { Color::Magenta }
-                    Level::ERROR => { Color::Red }
-                    Level::DEBUG => { Color::Blue }
-                }))];
-            if m.value.contains("\n") && wrap {
-                let n: Vec<_> = m.value.splitn(2, |c| c == '\n').collect();
-                content.push(Span::raw(n.get(0).unwrap().to_string()));
+                Span::styled(format!("{} ", m.timestamp.format("%+")), Style::default().fg(Color::Cyan)),
+                Span::styled(format!("{} ", system_label(m)), Style::default().fg(Color::Yellow)),
+                Span::styled(format!("{} ", m.level), Style::default().fg(level_color(m.level)))];
+            if show_fields {
+                m.fields.iter().for_each(|(k, v)| content.push(Span::styled(format!("{}={} ", k, v), Style::default().fg(Color::DarkGray))));
+            }
+            if value.contains("\n") && wrap {
+                let n: Vec<_> = value.splitn(2, '\n').collect();
+                content.push(Span::raw(n.first().unwrap().to_string()));
             } else {
                 let text2 = Text::from(Spans::from(content.clone()));
-                let take: String = m.value.chars().take(chunks[0].width as usize + 10 - text2.width()).collect();
+                let take: String = value.chars().take(chunks[0].width as usize + 10 - text2.width()).collect();
                 content.push(Span::raw(take.clone()));
             }
             let mut text = Text::from(Spans::from(content));
-            if m.value.contains("\n") && wrap {
-                let n: Vec<_> = m.value.splitn(2, |c| c == '\n').collect();
+            if value.contains("\n") && wrap {
+                let n: Vec<_> = value.splitn(2, '\n').collect();
                 text.extend(Text::raw(n.get(1).unwrap().to_string()));
             }
-            return text;
+            text
         }).rev().collect();
 
     let messages: Vec<_> = if wrap {
@@ -519,13 +832,14 @@ fn map_from_messages_to_text<'b>(chunks: &Vec<Rect>, messages: &'b Vec<Message>,
                     });
                     text1
                 }).collect();
-                x1
+                x1.into_iter().fold(Text::raw(""), |mut sum, t| {
+                    sum.extend(t);
+                    sum
+                })
             } else {
-                vec![m]
+                m
             }
-        }
-        )
-            .flatten().collect();
+        }).collect();
         messages
     } else {
         return messages;
@@ -533,23 +847,35 @@ fn map_from_messages_to_text<'b>(chunks: &Vec<Rect>, messages: &'b Vec<Message>,
     messages
 }
 
-//{"@timestamp": "2022-08-07T04:10:21+02", "message": "Message number 999999", "level": "INFO", "application": "appname"}
-#[derive(Deserialize, Serialize)]
-struct LogFormat {
-    #[serde(rename = "@timestamp")]
-    timestamp: String,
-    #[serde(default)]
-    stack: String,
-    #[serde(default)]
-    stack_trace: String,
-    message: String,
-    level: String,
-    application: String,
-}
-
 #[derive(Deserialize, Serialize)]
 struct Pod {
     name: String,
+    labels: BTreeMap<String, String>,
+    container: Option<String>,
+    init: bool,
+    /// Pod phase, or the reason the container is waiting or terminated
+    phase: String,
+    restarts: u32,
+    age: String,
+    crashloop: bool,
+}
+
+impl Pod {
+    fn named(name: String) -> Pod {
+        Pod { name, labels: BTreeMap::new(), container: None, init: false, phase: String::new(), restarts: 0, age: String::new(), crashloop: false }
+    }
+
+    fn label(&self) -> String {
+        let name = match (&self.container, self.init) {
+            (Some(c), true) => { format!("{} ── init {}", self.name, c) }
+            (Some(c), false) => { format!("{} ── {}", self.name, c) }
+            (None, _) => { self.name.clone() }
+        };
+        match self.phase.is_empty() {
+            true => { name }
+            false => { format!("{:<60} {:<18} {:>4} restarts {:>5}", name, self.phase, self.restarts, self.age) }
+        }
+    }
 }
 
 struct StatefulList<Pod> {
@@ -597,7 +923,7 @@ impl<Pod> StatefulList<Pod> {
 
 
     fn selected(&self) -> &HashSet<usize> {
-        return &self.selected;
+        &self.selected
     }
 
     fn select(&mut self) {
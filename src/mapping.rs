@@ -0,0 +1,216 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+use serde_with::{OneOrMany, serde_as};
+
+use crate::Level;
+use crate::diagnostics::Reject;
+use crate::timestamp;
+
+/// Which JSON keys fill the fields of a `Message`. Every field lists the keys to
+/// try in order, a key is a top level key or a dotted path into nested objects
+/// like `log.level`. All `stack` keys found are appended to the message.
+#[serde_as]
+#[derive(Deserialize, Clone)]
+pub struct FieldMapping {
+    #[serde(skip)]
+    pub(crate) name: String,
+    #[serde_as(as = "OneOrMany<_>")]
+    pub(crate) timestamp: Vec<String>,
+    #[serde_as(as = "OneOrMany<_>")]
+    pub(crate) message: Vec<String>,
+    #[serde_as(as = "OneOrMany<_>")]
+    pub(crate) level: Vec<String>,
+    #[serde_as(as = "OneOrMany<_>")]
+    #[serde(default)]
+    pub(crate) system: Vec<String>,
+    #[serde_as(as = "OneOrMany<_>")]
+    #[serde(default)]
+    pub(crate) stack: Vec<String>,
+}
+
+/// The fields a mapping found in a line
+pub struct Entry {
+    pub(crate) timestamp: DateTime<Utc>,
+    pub(crate) level: Level,
+    pub(crate) value: String,
+    pub(crate) system: Option<String>,
+    /// Everything else the line had, for formats that keep it
+    pub(crate) fields: BTreeMap<String, String>,
+}
+
+impl FieldMapping {
+    pub fn new(name: &str, timestamp: &[&str], message: &[&str], level: &[&str], system: &[&str], stack: &[&str]) -> FieldMapping {
+        let keys = |k: &[&str]| k.iter().map(|k| k.to_string()).collect();
+        FieldMapping {
+            name: name.to_string(),
+            timestamp: keys(timestamp),
+            message: keys(message),
+            level: keys(level),
+            system: keys(system),
+            stack: keys(stack),
+        }
+    }
+
+    /// The profiles that work without configuration, `default` first.
+    pub fn builtin() -> Vec<FieldMapping> {
+        vec![
+            FieldMapping::new("default", &["@timestamp"], &["message"], &["level"], &["application"], &["stack", "stack_trace"]),
+            FieldMapping::new("zap", &["ts"], &["msg"], &["level"], &["logger"], &["stacktrace"]),
+            FieldMapping::new("pino", &["time"], &["msg"], &["level"], &["name"], &["err.stack"]),
+            FieldMapping::new("ecs", &["@timestamp"], &["message"], &["log.level"], &["service.name"], &["error.stack_trace"]),
+            FieldMapping::new("logfmt", &["ts", "time", "t"], &["msg", "message"], &["level", "lvl"], &["app", "service", "logger"], &["stacktrace"]),
+        ]
+    }
+
+    /// Picks the fields out of a JSON line, fails when it lacks a timestamp, message or level.
+    /// The keys no field uses are kept, nested ones with their dotted path.
+    pub fn extract(&self, json: &Value) -> Result<Entry, Reject> {
+        let stack: String = self.stack.iter().filter_map(|k| lookup(json, k)).map(text).collect();
+        let mut fields = BTreeMap::new();
+        flatten("", json, &mut fields);
+        fields.retain(|k, _| !self.uses(k));
+        let timestamp = timestamp::parse(first(json, &self.timestamp).ok_or(Reject::NoTimestamp)?).ok_or(Reject::BadTimestamp)?;
+        let level = level(first(json, &self.level).ok_or(Reject::NoLevel)?).ok_or(Reject::UnknownLevel)?;
+        let mut message = text(first(json, &self.message).ok_or(Reject::NoMessage)?);
+        if let Some(embedded) = self.embedded(&message) {
+            message = embedded.0;
+            embedded.1.into_iter().for_each(|(k, v)| { fields.entry(k).or_insert(v); });
+        }
+        Ok(Entry {
+            timestamp,
+            level,
+            value: match stack.is_empty() {
+                true => { message }
+                false => { format!("{} {}", message, stack) }
+            },
+            system: first(json, &self.system).map(text),
+            fields,
+        })
+    }
+
+    /// A summary and the fields of a message that is a JSON object itself, like
+    /// `{"event":"order_created","id":42}`. The summary is the message of the object
+    /// when it has one, otherwise its fields as `key=value`.
+    fn embedded(&self, message: &str) -> Option<(String, BTreeMap<String, String>)> {
+        if !message.trim_start().starts_with('{') {
+            return None;
+        }
+        let json = serde_json::from_str::<Value>(message).ok().filter(|j| j.is_object())?;
+        let mut fields = BTreeMap::new();
+        flatten("", &json, &mut fields);
+        let summary = match self.message.iter().find(|k| fields.contains_key(*k)) {
+            Some(key) => { fields.remove(key).unwrap_or_default() }
+            None => {
+                fields.iter()
+                    .map(|(k, v)| match v.contains(' ') {
+                        true => { format!("{}={:?}", k, v) }
+                        false => { format!("{}={}", k, v) }
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            }
+        };
+        Some((summary, fields))
+    }
+
+    /// The built-in profile for logfmt lines of sources on the `default` profile.
+    pub fn logfmt() -> &'static FieldMapping {
+        static LOGFMT: OnceLock<FieldMapping> = OnceLock::new();
+        LOGFMT.get_or_init(|| FieldMapping::builtin().into_iter().find(|p| p.name == "logfmt").unwrap())
+    }
+
+    /// Whether `key` fills one of the fields.
+    pub fn uses(&self, key: &str) -> bool {
+        [&self.timestamp, &self.message, &self.level, &self.system, &self.stack].iter()
+            .any(|keys| keys.iter().any(|k| k == key))
+    }
+}
+
+/// Finds `path` in `json`, as a key of its own or as a path through nested objects.
+fn lookup<'a>(json: &'a Value, path: &str) -> Option<&'a Value> {
+    let object = json.as_object()?;
+    if let Some(value) = object.get(path) {
+        return Some(value).filter(|v| !v.is_null());
+    }
+    path.match_indices('.')
+        .find_map(|(i, _)| object.get(&path[..i]).and_then(|v| lookup(v, &path[i + 1..])))
+}
+
+fn flatten(prefix: &str, json: &Value, fields: &mut BTreeMap<String, String>) {
+    match json {
+        Value::Object(object) => {
+            object.iter().for_each(|(k, v)| match prefix.is_empty() {
+                true => { flatten(k, v, fields) }
+                false => { flatten(&format!("{}.{}", prefix, k), v, fields) }
+            });
+        }
+        Value::Null => {}
+        other => { fields.insert(prefix.to_string(), text(other)); }
+    }
+}
+
+fn first<'a>(json: &'a Value, paths: &[String]) -> Option<&'a Value> {
+    paths.iter().find_map(|p| lookup(json, p))
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => { s.clone() }
+        other => { other.to_string() }
+    }
+}
+
+/// A level name or alias in any case, or a pino/bunyan number
+pub(crate) fn level(value: &Value) -> Option<Level> {
+    match value {
+        Value::String(s) => { Level::from_str(s).ok() }
+        Value::Number(n) => { n.as_u64().map(Level::from_number) }
+        _ => { None }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::Level;
+    use crate::diagnostics::Reject;
+    use crate::mapping::FieldMapping;
+
+    fn profile(name: &str) -> FieldMapping {
+        FieldMapping::builtin().into_iter().find(|p| p.name == name).unwrap()
+    }
+
+    #[test]
+    fn extracts_with_profiles() {
+        let ecs = profile("ecs").extract(&json!({"@timestamp": "2022-08-07T04:10:21+02:00", "log.level": "warn", "message": "slow", "service": {"name": "shop"}})).unwrap();
+        assert_eq!(ecs.timestamp.to_rfc3339(), "2022-08-07T02:10:21+00:00");
+        assert!(ecs.level == Level::WARN);
+        assert_eq!(ecs.system.as_deref(), Some("shop"));
+        assert!(ecs.fields.is_empty());
+
+        let pino = profile("pino").extract(&json!({"time": 1659838221000u64, "level": 50, "msg": "failed", "err": {"stack": "Error: x"}})).unwrap();
+        assert_eq!(pino.timestamp.to_rfc3339(), "2022-08-07T02:10:21+00:00");
+        assert!(pino.level == Level::ERROR);
+        assert_eq!(pino.value, "failed Error: x");
+        assert!(pino.system.is_none());
+
+        let zap = profile("zap").extract(&json!({"ts": 1659838221.5, "level": "info", "msg": "ok", "traceId": "abc", "mdc": {"userId": 42}})).unwrap();
+        assert_eq!(zap.timestamp.timestamp_millis(), 1659838221500);
+        assert_eq!(zap.fields.into_iter().collect::<Vec<_>>(), vec![("mdc.userId".to_string(), "42".to_string()), ("traceId".to_string(), "abc".to_string())]);
+
+        let embedded = profile("default").extract(&json!({"@timestamp": "2022-08-07T04:10:21+02:00", "level": "info", "message": r#"{"event":"order_created","order":{"id":42},"note":"two words"}"#})).unwrap();
+        assert_eq!(embedded.value, r#"event=order_created note="two words" order.id=42"#);
+        assert_eq!(embedded.fields.get("order.id").map(|v| v.as_str()), Some("42"));
+        let summarized = profile("zap").extract(&json!({"ts": 1659838221.5, "level": "info", "msg": r#"{"msg":"created","id":7}"#, "id": 8})).unwrap();
+        assert_eq!(summarized.value, "created");
+        assert_eq!(summarized.fields.get("id").map(|v| v.as_str()), Some("8"));
+
+        assert!(profile("default").extract(&json!({"time": 1659838221000u64, "level": 50, "msg": "failed"})).err() == Some(Reject::NoTimestamp));
+    }
+}
@@ -1,4 +1,5 @@
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
@@ -6,15 +7,26 @@ use serde_with::formats::Flexible;
 use serde_with::TimestampMilliSeconds;
 
 use crate::Level;
+use crate::stack::StackTrace;
 
 #[serde_with::serde_as]
-#[derive(PartialEq, Eq, PartialOrd, Clone, Deserialize, Serialize)]
+#[derive(PartialEq, Eq, Clone, Deserialize, Serialize)]
 pub struct Message {
     #[serde_as(as = "TimestampMilliSeconds<String, Flexible>")]
     pub(crate) timestamp: DateTime<Utc>,
     pub(crate) system: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub(crate) namespace: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub(crate) container: String,
     pub(crate) level: Level,
     pub(crate) value: String,
+    /// What the line had besides the fields above, like `traceId` or the other logfmt pairs
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) fields: BTreeMap<String, String>,
+    /// The stack trace at the end of `value`, if it has one
+    #[serde(skip)]
+    pub(crate) stack: Option<StackTrace>,
 }
 
 impl Ord for Message {
@@ -22,3 +34,9 @@ impl Ord for Message {
         self.timestamp.cmp(&other.timestamp)
     }
 }
+
+impl PartialOrd for Message {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
@@ -0,0 +1,202 @@
+use std::sync::Arc;
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::thread::{JoinHandle, spawn};
+use std::time::Duration;
+
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::{CommandMessage, parse_and_send};
+use crate::envelope::Unwrapper;
+use crate::parse_send::{Event, Origin, send_held};
+use crate::source::SourceState;
+
+/// Lines of a Java stack trace that never start an event, whatever `start` says
+const CONTINUATION: &str = r"^(\s|Caused by:|Suppressed:|\.\.\. \d+ more)";
+/// The first line of a Java stack trace, it belongs to the event before it when frames follow
+const EXCEPTION: &str = r#"^(Exception in thread "[^"]*" )?([a-zA-Z_$][\w$]*\.)+[A-Z][\w$]*(Exception|Error|Throwable)(:.*)?$"#;
+const FRAME: &str = r"^\s+at ";
+
+/// `multiline` in `search.json`
+#[derive(Deserialize)]
+pub struct MultilineConfig {
+    start: Option<String>,
+    timeout_millis: Option<u64>,
+}
+
+/// Decides which lines start an event, the others are appended to the event before them
+pub struct Multiline {
+    start: Regex,
+    continuation: Regex,
+    exception: Regex,
+    frame: Regex,
+    /// How long an event waits for more lines before it is sent
+    timeout: Duration,
+}
+
+impl Default for Multiline {
+    fn default() -> Multiline {
+        Multiline {
+            start: Regex::new(r"^\S").unwrap(),
+            continuation: Regex::new(CONTINUATION).unwrap(),
+            exception: Regex::new(EXCEPTION).unwrap(),
+            frame: Regex::new(FRAME).unwrap(),
+            timeout: Duration::from_millis(500),
+        }
+    }
+}
+
+impl Multiline {
+    pub fn new(config: &MultilineConfig) -> Result<Multiline, String> {
+        let default = Multiline::default();
+        Ok(Multiline {
+            start: match &config.start {
+                Some(start) => { Regex::new(start).map_err(|e| format!("multiline start: {}", e))? }
+                None => { default.start }
+            },
+            timeout: config.timeout_millis.map(Duration::from_millis).unwrap_or(default.timeout),
+            ..default
+        })
+    }
+
+    /// JSON lines always are a whole event.
+    pub fn starts_event(&self, line: &str) -> bool {
+        line.starts_with('{') || (self.start.is_match(line) && !self.continuation.is_match(line))
+    }
+}
+
+/// Starts the next event with `line` and sends the one before it.
+fn next_event(event: &mut Option<Event>, line: Event, origin: &Origin, state: &SourceState, sender: &Sender<CommandMessage>) {
+    if let Some(e) = event.replace(line) {
+        parse_and_send(e, origin, state, sender);
+    }
+}
+
+/// Appends a line to the event or starts the next event with it.
+fn add_line(line: Event, event: &mut Option<Event>, exception: &mut Option<Event>, multiline: &Multiline, origin: &Origin, state: &SourceState, sender: &Sender<CommandMessage>) {
+    if let Some(x) = exception.take() {
+        match (&mut *event, multiline.frame.is_match(&line.text)) {
+            (Some(e), true) => {
+                e.text.push('\n');
+                e.text.push_str(&x.text);
+            }
+            _ => { next_event(event, x, origin, state, sender); }
+        }
+    }
+    match event {
+        Some(e) if !multiline.starts_event(&line.text) => {
+            e.text.push('\n');
+            e.text.push_str(&line.text);
+        }
+        Some(_) if multiline.exception.is_match(&line.text) => {
+            *exception = Some(line);
+        }
+        _ => { next_event(event, line, origin, state, sender); }
+    }
+}
+
+/// Joins the lines read by a source into events on a thread of its own, so an event
+/// is sent once the next one starts or no line followed it for the timeout. Lines of
+/// container runtimes are unwrapped first.
+pub struct Assembler {
+    lines: Sender<String>,
+    handle: JoinHandle<()>,
+}
+
+impl Assembler {
+    pub fn start(origin: &Origin, state: &Arc<SourceState>, sender: &Sender<CommandMessage>) -> Assembler {
+        let (lines, receiver) = channel::<String>();
+        let origin = origin.clone();
+        let state = state.clone();
+        let sender = sender.clone();
+        let handle = spawn(move || {
+            let multiline = state.multiline();
+            let mut unwrapper = Unwrapper::default();
+            let mut event: Option<Event> = None;
+            // An exception line waiting for the next line to tell whether it ends the event before it
+            let mut exception: Option<Event> = None;
+            loop {
+                match receiver.recv_timeout(multiline.timeout) {
+                    Ok(line) => {
+                        let (line, time) = match unwrapper.unwrap(line.trim_end_matches(['\r', '\n'])) {
+                            Some(l) => { l }
+                            None => { continue; }
+                        };
+                        let line = Event { text: line.trim_end().to_string(), time };
+                        add_line(line, &mut event, &mut exception, &multiline, &origin, &state, &sender);
+                    }
+                    Err(RecvTimeoutError::Timeout) => {
+                        if let Some(x) = exception.take() {
+                            next_event(&mut event, x, &origin, &state, &sender);
+                        }
+                        if let Some(e) = event.take() {
+                            parse_and_send(e, &origin, &state, &sender);
+                        }
+                        send_held(&origin, &state, &sender);
+                    }
+                    Err(RecvTimeoutError::Disconnected) => {
+                        // Chunks of lines the runtime never finished
+                        for (line, time) in unwrapper.finish() {
+                            let line = Event { text: line.trim_end().to_string(), time };
+                            add_line(line, &mut event, &mut exception, &multiline, &origin, &state, &sender);
+                        }
+                        if let Some(x) = exception.take() {
+                            next_event(&mut event, x, &origin, &state, &sender);
+                        }
+                        if let Some(e) = event.take() {
+                            parse_and_send(e, &origin, &state, &sender);
+                        }
+                        send_held(&origin, &state, &sender);
+                        return;
+                    }
+                }
+            }
+        });
+        Assembler { lines, handle }
+    }
+
+    pub fn push(&self, line: &str) {
+        let _ = self.lines.send(line.to_string());
+    }
+
+    /// Sends the last event and waits for it.
+    pub fn finish(self) {
+        drop(self.lines);
+        let _ = self.handle.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+
+    use crate::{CommandMessage, Level};
+    use crate::multiline::Assembler;
+    use crate::parse_send::Origin;
+    use crate::source::SourceState;
+
+    #[test]
+    fn appends_stack_traces() {
+        let (sender, receiver) = channel();
+        let assembler = Assembler::start(&Origin::new("test"), &SourceState::new(), &sender);
+        [
+            "ts=2022-08-07T02:10:21Z level=error msg=failed",
+            "java.lang.IllegalStateException: boom",
+            "\tat com.example.Shop.buy(Shop.java:42)",
+            "Caused by: java.io.IOException: gone",
+            "\t... 3 more",
+            "ts=2022-08-07T02:10:22Z level=info msg=ok",
+        ].iter().for_each(|l| assembler.push(l));
+        assembler.finish();
+        let messages: Vec<_> = receiver.try_iter().map(|m| match m {
+            CommandMessage::InsertJson(m) => { m }
+            _ => { panic!("not a message") }
+        }).collect();
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].level == Level::ERROR);
+        assert_eq!(messages[0].value.lines().count(), 5);
+        assert!(messages[0].value.starts_with("failed\njava.lang.IllegalStateException: boom\n"));
+        assert!(messages[0].stack.is_some());
+        assert_eq!(messages[1].value, "ok");
+    }
+}
@@ -1,39 +1,196 @@
-use std::str::FromStr;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
 use std::sync::mpsc::Sender;
 
 use chrono::{DateTime, Utc};
+use serde_json::Value;
 
-use crate::{CommandMessage, Level, LogFormat, Message};
+use crate::{CommandMessage, Level, Message};
+use crate::detect::{Candidates, detect, MIN_SAMPLES};
+use crate::diagnostics::Reject;
+use crate::logfmt;
+use crate::mapping::{Entry, FieldMapping};
+use crate::pattern::LinePattern;
+use crate::source::SourceState;
+use crate::stack;
 
-pub fn parse_and_send(x: &str, sender: &Sender<CommandMessage>) {
-    let result: Result<LogFormat, _> = serde_json::from_str(x);
-    let log_entry = match result {
-        Ok(l) => { l }
-        Err(_) => {
-            //  println!("{}", e.to_string());
-            return;
+/// Where a line was read from, fills in what the line itself doesn't tell
+#[derive(Clone, Default)]
+pub struct Origin {
+    /// Used when the line has no `application`
+    pub(crate) system: String,
+    pub(crate) namespace: String,
+    pub(crate) container: String,
+}
+
+impl Origin {
+    pub fn new(system: &str) -> Origin {
+        Origin { system: system.to_string(), ..Default::default() }
+    }
+}
+
+/// The lines of a source joined into an event
+#[derive(Clone)]
+pub struct Event {
+    pub(crate) text: String,
+    /// The time the container runtime wrapped the first line in, for events without one
+    pub(crate) time: Option<DateTime<Utc>>,
+}
+
+/// How the lines of a source are read
+#[derive(Clone)]
+pub enum Parser {
+    /// JSON for lines starting with `{`, then the first pattern that matches, then logfmt,
+    /// until enough lines were sampled to pick one of the others
+    Auto(Arc<Candidates>),
+    Json,
+    Logfmt,
+    Pattern(Arc<LinePattern>),
+    /// Every line as it is, with the time it was read
+    Raw,
+}
+
+impl fmt::Display for Parser {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Parser::Auto(_) => { write!(f, "auto") }
+            Parser::Json => { write!(f, "json") }
+            Parser::Logfmt => { write!(f, "logfmt") }
+            Parser::Pattern(p) => { write!(f, "{}", p.name) }
+            Parser::Raw => { write!(f, "raw") }
+        }
+    }
+}
+
+/// Parses an event with the parser and profile of the source and sends it to the search thread.
+/// Only the first line of an event is parsed, the lines after it are appended to the message.
+/// Events that can't be parsed are sent as they are, with the time of their wrapper or else
+/// the time they were read, and counted in the diagnostics of the source. On `auto` the
+/// first events are held back until there are enough of them to pick a parser.
+pub fn parse_and_send(x: Event, origin: &Origin, state: &SourceState, sender: &Sender<CommandMessage>) {
+    let event = Event { text: x.text.trim_end().to_string(), ..x };
+    if event.text.is_empty() {
+        return;
+    }
+    match state.parser().as_ref() {
+        Parser::Auto(candidates) => {
+            if let Some(events) = state.sample(event) {
+                decide(candidates, events, origin, state, sender);
+            }
+        }
+        parser => {
+            // Held while on `auto` before the parser was switched
+            state.take_samples().iter().for_each(|e| send(e, parser, origin, state, sender));
+            send(&event, parser, origin, state, sender);
+        }
+    }
+}
+
+/// Sends the events held back on `auto`, for a source that went quiet or ended.
+pub fn send_held(origin: &Origin, state: &SourceState, sender: &Sender<CommandMessage>) {
+    let events = state.take_samples();
+    if events.is_empty() {
+        return;
+    }
+    match state.parser().as_ref() {
+        Parser::Auto(candidates) => { decide(candidates, events, origin, state, sender) }
+        parser => { events.iter().for_each(|e| send(e, parser, origin, state, sender)) }
+    }
+}
+
+/// Picks the parser from the first lines of the events and sends them with it. A few lines
+/// that none reads don't rule out the others yet, they are read line by line.
+fn decide(candidates: &Arc<Candidates>, events: Vec<Event>, origin: &Origin, state: &SourceState, sender: &Sender<CommandMessage>) {
+    let lines: Vec<String> = events.iter().map(|e| e.text.lines().next().unwrap_or_default().to_string()).collect();
+    let parser = match detect(candidates, &lines, &state.mapping()) {
+        (Parser::Raw, _) if lines.len() < MIN_SAMPLES => { Parser::Auto(candidates.clone()) }
+        (parser, profile) => {
+            state.set_detected(parser.clone(), profile);
+            parser
         }
     };
-    let dt = DateTime::parse_from_rfc3339(log_entry.timestamp.as_str());
-    match dt {
-        Ok(time) => {
-            let time = time.with_timezone(&Utc);
-            let m = Message {
-                timestamp: time,
-                value: format!("{} {}{}", log_entry.message, log_entry.stack, log_entry.stack_trace),
-                system: log_entry.application,
-                level: match Level::from_str(&log_entry.level) {
-                    Ok(s) => { s }
-                    Err(_) => { return; }
-                },
-            };
-            match sender.send(CommandMessage::InsertJson(m)) {
-                Ok(_) => {}
-                Err(_) => { return; }
+    events.iter().for_each(|e| send(e, &parser, origin, state, sender));
+}
+
+fn send(event: &Event, parser: &Parser, origin: &Origin, state: &SourceState, sender: &Sender<CommandMessage>) {
+    let time = event.time.unwrap_or_else(Utc::now);
+    let (line, rest) = match event.text.split_once('\n') {
+        Some((line, rest)) => { (line, Some(rest)) }
+        None => { (event.text.as_str(), None) }
+    };
+    let mapping = state.mapping();
+    let entry = match parser {
+        Parser::Json => { parse_json(line, &mapping) }
+        Parser::Logfmt => { parse_logfmt(line, &mapping) }
+        Parser::Pattern(p) => { p.parse(line) }
+        Parser::Raw => {
+            Ok(Entry { timestamp: time, level: Level::UNPARSED, value: line.to_string(), system: None, fields: BTreeMap::new() })
+        }
+        Parser::Auto(_) if line.trim_start().starts_with('{') => { parse_json(line, &mapping) }
+        Parser::Auto(candidates) => {
+            match candidates.patterns.iter().find(|p| p.matches(line)) {
+                Some(p) => { p.parse(line) }
+                None => {
+                    parse_logfmt(line, &mapping).map_err(|r| match r {
+                        Reject::NotLogfmt => { Reject::NotRecognized }
+                        r => { r }
+                    })
+                }
+            }
+        }
+    };
+    let m = match entry {
+        Ok(entry) => {
+            let value = match rest {
+                Some(rest) => { format!("{}\n{}", entry.value, rest) }
+                None => { entry.value }
             };
+            Message {
+                timestamp: entry.timestamp,
+                stack: stack::parse(&value),
+                value,
+                system: entry.system.unwrap_or_else(|| origin.system.clone()),
+                namespace: origin.namespace.clone(),
+                container: origin.container.clone(),
+                level: entry.level,
+                fields: entry.fields,
+            }
         }
-        Err(_) => {
-            //   println!("{}", e);
+        Err(reason) => {
+            state.reject(reason, &event.text);
+            Message {
+                timestamp: time,
+                value: event.text.clone(),
+                system: origin.system.clone(),
+                namespace: origin.namespace.clone(),
+                container: origin.container.clone(),
+                level: Level::UNPARSED,
+                fields: BTreeMap::new(),
+                stack: stack::parse(&event.text),
+            }
         }
+    };
+    let _ = sender.send(CommandMessage::InsertJson(m));
+}
+
+pub(crate) fn parse_json(line: &str, mapping: &FieldMapping) -> Result<Entry, Reject> {
+    match serde_json::from_str::<Value>(line) {
+        Ok(json) => { mapping.extract(&json) }
+        Err(_) => { Err(Reject::NotJson) }
     }
 }
+
+/// Reads a logfmt line with the profile, the pairs the profile doesn't use are kept as fields.
+pub(crate) fn parse_logfmt(line: &str, mapping: &FieldMapping) -> Result<Entry, Reject> {
+    let pairs = logfmt::parse(line).ok_or(Reject::NotLogfmt)?;
+    // The JSON keys of `default` are rarely used with logfmt
+    let mapping = match mapping.name == "default" {
+        true => { FieldMapping::logfmt() }
+        false => { mapping }
+    };
+    let json = Value::Object(pairs.iter().map(|(k, v)| (k.clone(), Value::String(v.clone()))).collect());
+    let mut entry = mapping.extract(&json)?;
+    entry.fields.extend(pairs.into_iter().filter(|(k, _)| !mapping.uses(k)));
+    Ok(entry)
+}
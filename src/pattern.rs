@@ -0,0 +1,93 @@
+use std::collections::BTreeMap;
+
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::diagnostics::Reject;
+use crate::Level;
+use crate::mapping::{Entry, level};
+use crate::timestamp::{parse_str, parse_with_format};
+
+/// Groups that fill the fields of a `Message`, any other named group becomes a field
+const GROUPS: [&str; 4] = ["timestamp", "level", "system", "message"];
+
+/// A pattern as written in `search.json`
+#[derive(Deserialize)]
+pub struct PatternConfig {
+    regex: String,
+    /// chrono format of the `timestamp` group, without offset it is in the configured timezone
+    timestamp_format: Option<String>,
+}
+
+/// Reads plain text lines with a regex with named groups `timestamp`, `level`,
+/// `system` and `message`. Lines without `level` are INFO, without `message` the
+/// whole line is the message.
+pub struct LinePattern {
+    pub(crate) name: String,
+    regex: Regex,
+    timestamp_format: Option<String>,
+}
+
+impl LinePattern {
+    pub fn new(name: &str, config: &PatternConfig) -> Result<LinePattern, String> {
+        let regex = Regex::new(&config.regex).map_err(|e| format!("pattern {}: {}", name, e))?;
+        if !regex.capture_names().any(|n| n == Some("timestamp")) {
+            return Err(format!("pattern {} has no timestamp group", name));
+        }
+        Ok(LinePattern { name: name.to_string(), regex, timestamp_format: config.timestamp_format.clone() })
+    }
+
+    pub fn matches(&self, line: &str) -> bool {
+        self.regex.is_match(line)
+    }
+
+    pub fn parse(&self, line: &str) -> Result<Entry, Reject> {
+        let captures = self.regex.captures(line).ok_or(Reject::NoPatternMatch)?;
+        let group = |name: &str| captures.name(name).map(|m| m.as_str());
+        let timestamp = group("timestamp").ok_or(Reject::NoTimestamp)?;
+        let timestamp = match &self.timestamp_format {
+            Some(format) => { parse_with_format(timestamp, format) }
+            None => { parse_str(timestamp) }
+        }.ok_or(Reject::BadTimestamp)?;
+        let level = match group("level") {
+            Some(l) => { level(&Value::String(l.to_string())).ok_or(Reject::UnknownLevel)? }
+            None => { Level::INFO }
+        };
+        let fields: BTreeMap<String, String> = self.regex.capture_names().flatten()
+            .filter(|n| !GROUPS.contains(n))
+            .filter_map(|n| group(n).map(|v| (n.to_string(), v.to_string())))
+            .collect();
+        Ok(Entry {
+            timestamp,
+            level,
+            value: group("message").unwrap_or(line).to_string(),
+            system: group("system").map(|s| s.to_string()),
+            fields,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, TimeZone, Utc};
+
+    use crate::Level;
+    use crate::pattern::{LinePattern, PatternConfig};
+
+    #[test]
+    fn parses_spring_boot_lines() {
+        let config = PatternConfig {
+            regex: r"^(?P<timestamp>\S+ \S+)\s+(?P<level>\w+) \d+ --- \[\s*(?P<thread>[^\]]+)\] (?P<system>\S+)\s+: (?P<message>.*)$".to_string(),
+            timestamp_format: Some("%Y-%m-%d %H:%M:%S%.3f".to_string()),
+        };
+        let pattern = LinePattern::new("spring", &config).unwrap();
+        let entry = pattern.parse("2022-08-07 04:10:21.123  WARN 1 --- [           main] o.s.b.StartupInfoLogger                  : Started in 3.1 seconds").unwrap();
+        assert_eq!(entry.timestamp, Local.ymd(2022, 8, 7).and_hms_milli(4, 10, 21, 123).with_timezone(&Utc));
+        assert!(entry.level == Level::WARN);
+        assert_eq!(entry.system.as_deref(), Some("o.s.b.StartupInfoLogger"));
+        assert_eq!(entry.value, "Started in 3.1 seconds");
+        assert_eq!(entry.fields.get("thread").map(|s| s.as_str()), Some("main"));
+        assert!(pattern.parse("Exception in thread main").is_err());
+    }
+}
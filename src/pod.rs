@@ -1,2 +1,4 @@
+pub mod cluster;
+pub mod kube;
 pub mod pods;
 pub mod populate_pods;
\ No newline at end of file
@@ -0,0 +1,220 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Deserialize;
+
+use crate::pod::kube::{Client, KubeError, Kubeconfig, StreamHandle};
+use crate::pod::pods::{Metadata, Pods};
+
+/// Labels that change with every rollout and so can't select the pods of a deployment
+const ROLLOUT_LABELS: [&str; 4] = ["pod-template-hash", "controller-revision-hash", "pod-template-generation", "statefulset.kubernetes.io/pod-name"];
+
+/// The cluster context and namespace to talk to, `None` is whatever the kubeconfig makes current
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct KubeTarget {
+    pub(crate) context: Option<String>,
+    pub(crate) namespace: Option<String>,
+}
+
+/// The clients by context, building one reads the kubeconfig and may run a credential plugin
+fn clients() -> &'static Mutex<HashMap<Option<String>, Arc<Client>>> {
+    static CLIENTS: OnceLock<Mutex<HashMap<Option<String>, Arc<Client>>>> = OnceLock::new();
+    CLIENTS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+impl KubeTarget {
+    /// The client of the target's context, built once and shared by everything talking to it.
+    pub fn client(&self) -> Result<Arc<Client>, KubeError> {
+        let mut clients = clients().lock().unwrap();
+        if let Some(client) = clients.get(&self.context) {
+            return Ok(client.clone());
+        }
+        let client = Arc::new(Client::new(&Kubeconfig::load()?, self.context.as_deref())?);
+        clients.insert(self.context.clone(), client.clone());
+        Ok(client)
+    }
+
+    /// Calls the cluster with the shared client, and once more with a new one when the
+    /// token was rejected, it may have expired.
+    fn with_client<T>(&self, call: impl Fn(&Client) -> Result<T, KubeError>) -> Result<T, KubeError> {
+        let client = self.client()?;
+        match call(&client) {
+            Err(e) if e.is_unauthorized() => {
+                {
+                    let mut clients = clients().lock().unwrap();
+                    // Unless another call has built a new one already
+                    if clients.get(&self.context).is_some_and(|c| Arc::ptr_eq(c, &client)) {
+                        clients.remove(&self.context);
+                    }
+                }
+                call(&*self.client()?)
+            }
+            result => { result }
+        }
+    }
+
+    /// The same target with the namespace filled in from the kubeconfig when not given.
+    pub fn resolved(&self) -> KubeTarget {
+        match self.namespace {
+            Some(_) => { self.clone() }
+            None => {
+                KubeTarget { context: self.context.clone(), namespace: current_namespace(self) }
+            }
+        }
+    }
+
+    fn namespace_of(&self, client: &Client) -> String {
+        self.namespace.clone().unwrap_or_else(|| client.namespace().to_string())
+    }
+}
+
+/// What `pod_logs` asks for, see `oc logs --help`
+#[derive(Default)]
+pub struct LogOptions {
+    pub(crate) container: Option<String>,
+    pub(crate) follow: bool,
+    pub(crate) previous: bool,
+    pub(crate) since_time: Option<DateTime<Utc>>,
+    pub(crate) since_seconds: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct Deployment {
+    spec: DeploymentSpec,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DeploymentSpec {
+    selector: LabelSelector,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LabelSelector {
+    #[serde(default)]
+    match_labels: BTreeMap<String, String>,
+}
+
+/// Any list of API objects where only the names matter, namespaces or projects
+#[derive(Deserialize)]
+struct NameList {
+    items: Vec<Named>,
+}
+
+#[derive(Deserialize)]
+struct Named {
+    metadata: Metadata,
+}
+
+/// The pods of the target's namespace, optionally restricted by a label selector.
+pub fn list_pods(target: &KubeTarget, selector: Option<&str>) -> Result<Pods, String> {
+    Ok(target.with_client(|client| {
+        let path = format!("/api/v1/namespaces/{}/pods", target.namespace_of(client));
+        match selector {
+            Some(selector) => { client.get(&path, &[("labelSelector", selector)]) }
+            None => { client.get(&path, &[]) }
+        }
+    })?)
+}
+
+/// The label selector of a deployment, `app=web,tier=front`.
+pub fn deployment_selector(target: &KubeTarget, name: &str) -> Result<String, String> {
+    let deployment: Deployment = target.with_client(|client| {
+        client.get(&format!("/apis/apps/v1/namespaces/{}/deployments/{}", target.namespace_of(client), name), &[])
+    })?;
+    match deployment.spec.selector.match_labels.is_empty() {
+        true => { Err(format!("deployment {} has no matchLabels", name)) }
+        false => { Ok(to_selector(&deployment.spec.selector.match_labels)) }
+    }
+}
+
+/// Streams the log of a pod container, every line starts with its RFC3339 timestamp.
+pub fn pod_logs(target: &KubeTarget, pod: &str, options: &LogOptions) -> Result<(Box<dyn Read + Send + Sync>, StreamHandle), KubeError> {
+    let since_time = options.since_time.map(|t| t.to_rfc3339_opts(SecondsFormat::Secs, true));
+    let since_seconds = options.since_seconds.map(|s| s.to_string());
+    let mut query = vec![("timestamps", "true")];
+    if let Some(container) = &options.container {
+        query.push(("container", container));
+    }
+    if options.follow {
+        query.push(("follow", "true"));
+    }
+    if options.previous {
+        query.push(("previous", "true"));
+    }
+    if let Some(since_time) = &since_time {
+        query.push(("sinceTime", since_time));
+    }
+    if let Some(since_seconds) = &since_seconds {
+        query.push(("sinceSeconds", since_seconds));
+    }
+    target.with_client(|client| client.stream(&format!("/api/v1/namespaces/{}/pods/{}/log", target.namespace_of(client), pod), &query))
+}
+
+pub fn list_contexts() -> Result<Vec<String>, String> {
+    Ok(Kubeconfig::load()?.context_names())
+}
+
+pub fn current_context() -> Option<String> {
+    Kubeconfig::load().ok()?.current_context().map(|c| c.to_string())
+}
+
+/// Namespaces of the target's context, the projects we have access to when we can't list them all.
+pub fn list_namespaces(target: &KubeTarget) -> Result<Vec<String>, String> {
+    let names = target.with_client(|client| {
+        match client.get::<NameList>("/api/v1/namespaces", &[]) {
+            Ok(names) => { Ok(names) }
+            Err(_) => { client.get::<NameList>("/apis/project.openshift.io/v1/projects", &[]) }
+        }
+    })?;
+    Ok(names.items.into_iter().map(|n| n.metadata.name).collect())
+}
+
+/// The namespace the kubeconfig context points at, `default` when it names none.
+pub fn current_namespace(target: &KubeTarget) -> Option<String> {
+    let config = Kubeconfig::load().ok()?;
+    let context = config.context(target.context.as_deref()).ok()?;
+    Some(context.namespace.clone().unwrap_or_else(|| "default".to_string()))
+}
+
+/// A selector matching the pods with the same labels across rollouts.
+pub fn stable_selector(labels: &BTreeMap<String, String>) -> Option<String> {
+    let mut labels: BTreeMap<String, String> = labels.iter()
+        .filter(|(k, _)| !ROLLOUT_LABELS.contains(&k.as_str()))
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    // OpenShift deployment configs label every rollout with its own `deployment`
+    if labels.contains_key("deploymentconfig") {
+        labels.remove("deployment");
+    }
+    match labels.is_empty() {
+        true => { None }
+        false => { Some(to_selector(&labels)) }
+    }
+}
+
+fn to_selector(labels: &BTreeMap<String, String>) -> String {
+    labels.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::pod::cluster::stable_selector;
+
+    fn labels(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn drops_rollout_labels() {
+        assert_eq!(stable_selector(&labels(&[("app", "shop"), ("pod-template-hash", "5d8f7c"), ("tier", "web")])), Some("app=shop,tier=web".to_string()));
+        assert_eq!(stable_selector(&labels(&[("deployment", "shop-3"), ("deploymentconfig", "shop")])), Some("deploymentconfig=shop".to_string()));
+        assert_eq!(stable_selector(&labels(&[("deployment", "shop-3")])), Some("deployment=shop-3".to_string()));
+        assert_eq!(stable_selector(&labels(&[("controller-revision-hash", "7b9"), ("statefulset.kubernetes.io/pod-name", "db-0")])), None);
+    }
+}
@@ -0,0 +1,593 @@
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::net::{Shutdown, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, SignatureScheme};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use ureq::{ReadWrite, TlsConnector};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Why a call to the cluster failed
+#[derive(Debug)]
+pub enum KubeError {
+    /// The kubeconfig is missing or doesn't describe the context
+    Config(String),
+    /// The API server couldn't be reached
+    Transport(String),
+    /// The API server answered with an error `Status`
+    Api { code: u16, reason: String, message: String },
+}
+
+impl KubeError {
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, KubeError::Api { code: 404, .. })
+    }
+
+    /// The token was rejected, an expired one is renewed by building the client again.
+    pub fn is_unauthorized(&self) -> bool {
+        matches!(self, KubeError::Api { code: 401, .. })
+    }
+}
+
+impl fmt::Display for KubeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KubeError::Config(m) => { write!(f, "kubeconfig: {}", m) }
+            KubeError::Transport(m) => { write!(f, "{}", m) }
+            KubeError::Api { code, reason, message } => { write!(f, "{} {}: {}", code, reason, message) }
+        }
+    }
+}
+
+impl From<KubeError> for String {
+    fn from(e: KubeError) -> String {
+        e.to_string()
+    }
+}
+
+/// The parts of a kubeconfig needed to reach a cluster, see `kubectl config view`.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct Kubeconfig {
+    #[serde(default)]
+    current_context: Option<String>,
+    #[serde(default)]
+    clusters: Vec<NamedCluster>,
+    #[serde(default)]
+    contexts: Vec<NamedContext>,
+    #[serde(default)]
+    users: Vec<NamedUser>,
+}
+
+#[derive(Deserialize)]
+struct NamedCluster {
+    name: String,
+    cluster: Cluster,
+}
+
+#[derive(Deserialize)]
+struct NamedContext {
+    name: String,
+    context: Context,
+}
+
+#[derive(Deserialize)]
+struct NamedUser {
+    name: String,
+    user: User,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Cluster {
+    server: String,
+    certificate_authority: Option<PathBuf>,
+    certificate_authority_data: Option<String>,
+    #[serde(default)]
+    insecure_skip_tls_verify: bool,
+}
+
+#[derive(Deserialize)]
+pub struct Context {
+    cluster: String,
+    #[serde(default)]
+    user: String,
+    pub(crate) namespace: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+struct User {
+    token: Option<String>,
+    #[serde(rename = "tokenFile")]
+    token_file: Option<PathBuf>,
+    client_certificate: Option<PathBuf>,
+    client_certificate_data: Option<String>,
+    client_key: Option<PathBuf>,
+    client_key_data: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+    exec: Option<Exec>,
+}
+
+/// A credential plugin, prints an `ExecCredential` with the token
+#[derive(Deserialize)]
+struct Exec {
+    command: String,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    env: Option<Vec<EnvVar>>,
+}
+
+#[derive(Deserialize)]
+struct EnvVar {
+    name: String,
+    value: String,
+}
+
+#[derive(Deserialize)]
+struct ExecCredential {
+    status: ExecStatus,
+}
+
+#[derive(Deserialize)]
+struct ExecStatus {
+    token: Option<String>,
+}
+
+/// The error body the API server sends along with a failed request
+#[derive(Deserialize)]
+struct Status {
+    #[serde(default)]
+    reason: String,
+    #[serde(default)]
+    message: String,
+}
+
+impl Kubeconfig {
+    /// Reads the files in `$KUBECONFIG`, or `~/.kube/config`. Where several files
+    /// define the same name the first one wins, like kubectl does.
+    pub fn load() -> Result<Kubeconfig, KubeError> {
+        let paths: Vec<PathBuf> = match std::env::var_os("KUBECONFIG") {
+            Some(p) if !p.is_empty() => { std::env::split_paths(&p).collect() }
+            _ => { home().map(|h| vec![h.join(".kube").join("config")]).unwrap_or_default() }
+        };
+        let mut merged = Kubeconfig::default();
+        for path in paths.iter().filter(|p| p.exists()) {
+            merged.merge(Kubeconfig::read(path)?);
+        }
+        Ok(merged)
+    }
+
+    pub fn read(path: &Path) -> Result<Kubeconfig, KubeError> {
+        let text = fs::read_to_string(path).map_err(|e| KubeError::Config(format!("{}: {}", path.display(), e)))?;
+        let mut config: Kubeconfig = serde_yaml::from_str(&text).map_err(|e| KubeError::Config(format!("{}: {}", path.display(), e)))?;
+        // Files named in a kubeconfig are relative to the kubeconfig
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        config.clusters.iter_mut().for_each(|c| relative_to(dir, &mut c.cluster.certificate_authority));
+        config.users.iter_mut().for_each(|u| {
+            relative_to(dir, &mut u.user.token_file);
+            relative_to(dir, &mut u.user.client_certificate);
+            relative_to(dir, &mut u.user.client_key);
+        });
+        Ok(config)
+    }
+
+    fn merge(&mut self, other: Kubeconfig) {
+        if self.current_context.is_none() {
+            self.current_context = other.current_context;
+        }
+        for c in other.clusters {
+            if !self.clusters.iter().any(|m| m.name == c.name) {
+                self.clusters.push(c);
+            }
+        }
+        for c in other.contexts {
+            if !self.contexts.iter().any(|m| m.name == c.name) {
+                self.contexts.push(c);
+            }
+        }
+        for u in other.users {
+            if !self.users.iter().any(|m| m.name == u.name) {
+                self.users.push(u);
+            }
+        }
+    }
+
+    pub fn context_names(&self) -> Vec<String> {
+        self.contexts.iter().map(|c| c.name.clone()).collect()
+    }
+
+    pub fn current_context(&self) -> Option<&str> {
+        self.current_context.as_deref().filter(|c| !c.is_empty())
+    }
+
+    /// The named context, or the current one.
+    pub fn context(&self, name: Option<&str>) -> Result<&Context, KubeError> {
+        let name = name.or_else(|| self.current_context())
+            .ok_or_else(|| KubeError::Config("no current context".to_string()))?;
+        self.contexts.iter().find(|c| c.name == name)
+            .map(|c| &c.context)
+            .ok_or_else(|| KubeError::Config(format!("context {} not found", name)))
+    }
+}
+
+fn home() -> Option<PathBuf> {
+    std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE")).map(PathBuf::from)
+}
+
+fn relative_to(dir: &Path, path: &mut Option<PathBuf>) {
+    if let Some(p) = path.as_mut() {
+        if p.is_relative() {
+            *p = dir.join(&p);
+        }
+    }
+}
+
+/// Talks to the API server of one kubeconfig context.
+pub struct Client {
+    agent: ureq::Agent,
+    tls: Option<Arc<rustls::ClientConfig>>,
+    server: String,
+    authorization: Option<String>,
+    namespace: String,
+}
+
+impl Client {
+    pub fn new(config: &Kubeconfig, context: Option<&str>) -> Result<Client, KubeError> {
+        let context = config.context(context)?;
+        let cluster = config.clusters.iter().find(|c| c.name == context.cluster)
+            .map(|c| &c.cluster)
+            .ok_or_else(|| KubeError::Config(format!("cluster {} not found", context.cluster)))?;
+        let default_user = User::default();
+        let user = config.users.iter().find(|u| u.name == context.user)
+            .map(|u| &u.user)
+            .unwrap_or(&default_user);
+        let tls = match cluster.server.starts_with("https:") {
+            true => { Some(Arc::new(tls_config(cluster, user)?)) }
+            false => { None }
+        };
+        let mut agent = ureq::AgentBuilder::new().timeout_connect(CONNECT_TIMEOUT);
+        if let Some(tls) = &tls {
+            agent = agent.tls_config(tls.clone());
+        }
+        Ok(Client {
+            agent: agent.build(),
+            tls,
+            server: cluster.server.trim_end_matches('/').to_string(),
+            authorization: authorization(user)?,
+            namespace: context.namespace.clone().unwrap_or_else(|| "default".to_string()),
+        })
+    }
+
+    /// The namespace of the context, `default` when it doesn't name one.
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// GETs `path` and parses the JSON answer.
+    pub fn get<T: DeserializeOwned>(&self, path: &str, query: &[(&str, &str)]) -> Result<T, KubeError> {
+        serde_json::from_reader(self.call(&self.agent, &self.server, path, query)?.into_reader()).map_err(|e| KubeError::Transport(e.to_string()))
+    }
+
+    /// GETs `path` and hands back the body as it arrives, for `follow=true`, with the
+    /// handle that ends it.
+    pub fn stream(&self, path: &str, query: &[(&str, &str)]) -> Result<(Box<dyn Read + Send + Sync>, StreamHandle), KubeError> {
+        // A connection of its own, so the socket the connector sees is the one of this stream
+        let connector = Connector { tls: self.tls.clone(), socket: Arc::new(Mutex::new(None)) };
+        let socket = connector.socket.clone();
+        let agent = ureq::AgentBuilder::new().timeout_connect(CONNECT_TIMEOUT).tls_connector(Arc::new(connector)).build();
+        let server = match self.tls {
+            Some(_) => { self.server.clone() }
+            None => { plain_as_https(&self.server) }
+        };
+        let response = self.call(&agent, &server, path, query)?;
+        let socket = socket.lock().unwrap().take();
+        Ok((response.into_reader(), StreamHandle(socket)))
+    }
+
+    fn call(&self, agent: &ureq::Agent, server: &str, path: &str, query: &[(&str, &str)]) -> Result<ureq::Response, KubeError> {
+        let mut request = agent.get(&format!("{}{}", server, path));
+        for (k, v) in query {
+            request = request.query(k, v);
+        }
+        if let Some(authorization) = &self.authorization {
+            request = request.set("Authorization", authorization);
+        }
+        match request.call() {
+            Ok(r) => { Ok(r) }
+            Err(ureq::Error::Status(code, response)) => {
+                let status_text = response.status_text().to_string();
+                match serde_json::from_reader::<_, Status>(response.into_reader()) {
+                    Ok(s) => { Err(KubeError::Api { code, reason: s.reason, message: s.message }) }
+                    Err(_) => { Err(KubeError::Api { code, reason: status_text, message: path.to_string() }) }
+                }
+            }
+            Err(ureq::Error::Transport(t)) => { Err(KubeError::Transport(t.to_string())) }
+        }
+    }
+}
+
+/// Ends a streamed response from another thread, the read blocked on it returns.
+pub struct StreamHandle(Option<TcpStream>);
+
+impl StreamHandle {
+    pub fn shutdown(&self) {
+        if let Some(socket) = &self.0 {
+            let _ = socket.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+/// Keeps a handle on the socket of the connection and puts TLS on it, or leaves it
+/// plain for `http:` servers. ureq only hands the connections of `https:` URLs to a
+/// connector, so streams from plain servers are asked for as `https:`.
+struct Connector {
+    tls: Option<Arc<rustls::ClientConfig>>,
+    socket: Arc<Mutex<Option<TcpStream>>>,
+}
+
+impl TlsConnector for Connector {
+    fn connect(&self, dns_name: &str, io: Box<dyn ReadWrite>) -> Result<Box<dyn ReadWrite>, ureq::Error> {
+        *self.socket.lock().unwrap() = io.socket().and_then(|s| s.try_clone().ok());
+        match &self.tls {
+            Some(tls) => { TlsConnector::connect(tls, dns_name, io) }
+            None => { Ok(io) }
+        }
+    }
+}
+
+/// `http://host/api` as `https://host:80/api`, the port of `http:` made explicit.
+fn plain_as_https(server: &str) -> String {
+    let rest = server.trim_start_matches("http://");
+    let (authority, path) = match rest.find('/') {
+        Some(i) => { rest.split_at(i) }
+        None => { (rest, "") }
+    };
+    match authority.rsplit_once(':').is_some_and(|(_, port)| port.parse::<u16>().is_ok()) {
+        true => { format!("https://{}{}", authority, path) }
+        false => { format!("https://{}:80{}", authority, path) }
+    }
+}
+
+/// The `Authorization` header for a token, a credential plugin or basic auth.
+fn authorization(user: &User) -> Result<Option<String>, KubeError> {
+    if let Some(token) = &user.token {
+        return Ok(Some(format!("Bearer {}", token)));
+    }
+    if let Some(file) = &user.token_file {
+        let token = fs::read_to_string(file).map_err(|e| KubeError::Config(format!("{}: {}", file.display(), e)))?;
+        return Ok(Some(format!("Bearer {}", token.trim())));
+    }
+    if let Some(exec) = &user.exec {
+        let mut command = Command::new(&exec.command);
+        command.args(&exec.args);
+        exec.env.iter().flatten().for_each(|e| { command.env(&e.name, &e.value); });
+        let output = command.output().map_err(|e| KubeError::Config(format!("{}: {}", exec.command, e)))?;
+        if !output.status.success() {
+            return Err(KubeError::Config(format!("{}: {}", exec.command, String::from_utf8_lossy(&output.stderr).trim())));
+        }
+        let credential: ExecCredential = serde_json::from_slice(&output.stdout).map_err(|e| KubeError::Config(format!("{}: {}", exec.command, e)))?;
+        return Ok(credential.status.token.map(|t| format!("Bearer {}", t)));
+    }
+    match (&user.username, &user.password) {
+        (Some(u), Some(p)) => { Ok(Some(format!("Basic {}", STANDARD.encode(format!("{}:{}", u, p))))) }
+        _ => { Ok(None) }
+    }
+}
+
+/// PEM from the inline base64 `-data` field or from the file it names.
+fn pem(data: &Option<String>, file: &Option<PathBuf>) -> Result<Option<Vec<u8>>, KubeError> {
+    match (data, file) {
+        (Some(data), _) => {
+            STANDARD.decode(data.trim()).map(Some).map_err(|e| KubeError::Config(e.to_string()))
+        }
+        (None, Some(file)) => {
+            fs::read(file).map(Some).map_err(|e| KubeError::Config(format!("{}: {}", file.display(), e)))
+        }
+        (None, None) => { Ok(None) }
+    }
+}
+
+fn tls_config(cluster: &Cluster, user: &User) -> Result<rustls::ClientConfig, KubeError> {
+    let tls = |e: rustls::Error| KubeError::Config(e.to_string());
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = rustls::ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(tls)?;
+    let builder = match cluster.insecure_skip_tls_verify {
+        true => { builder.dangerous().with_custom_certificate_verifier(Arc::new(SkipVerify(provider))) }
+        false => {
+            let mut roots = rustls::RootCertStore::empty();
+            match pem(&cluster.certificate_authority_data, &cluster.certificate_authority)? {
+                Some(ca) => {
+                    for cert in rustls_pemfile::certs(&mut ca.as_slice()) {
+                        roots.add(cert.map_err(|e| KubeError::Config(e.to_string()))?).map_err(tls)?;
+                    }
+                }
+                None => { roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()) }
+            }
+            builder.with_root_certificates(roots)
+        }
+    };
+    let cert = pem(&user.client_certificate_data, &user.client_certificate)?;
+    let key = pem(&user.client_key_data, &user.client_key)?;
+    match (cert, key) {
+        (Some(cert), Some(key)) => {
+            let certs = rustls_pemfile::certs(&mut cert.as_slice())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| KubeError::Config(e.to_string()))?;
+            let key = rustls_pemfile::private_key(&mut key.as_slice())
+                .map_err(|e| KubeError::Config(e.to_string()))?
+                .ok_or_else(|| KubeError::Config("client-key has no private key".to_string()))?;
+            builder.with_client_auth_cert(certs, key).map_err(tls)
+        }
+        _ => { Ok(builder.with_no_client_auth()) }
+    }
+}
+
+/// Accepts any server certificate, for `insecure-skip-tls-verify`.
+#[derive(Debug)]
+struct SkipVerify(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for SkipVerify {
+    fn verify_server_cert(&self, _: &CertificateDer<'_>, _: &[CertificateDer<'_>], _: &ServerName<'_>, _: &[u8], _: UnixTime) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc::{channel, Receiver};
+    use std::thread::spawn;
+
+    use crate::pod::kube::{Client, Kubeconfig};
+    use crate::pod::pods::Pods;
+
+    /// Answers `responses` in order, one connection each, and passes on the request lines it saw.
+    fn mock_server(responses: Vec<String>) -> (String, Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let (tx, rx) = channel();
+        spawn(move || {
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    request.push_str(&line);
+                }
+                tx.send(request).unwrap();
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        (address, rx)
+    }
+
+    fn response(status: &str, body: &str) -> String {
+        format!("HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body)
+    }
+
+    fn client(server: &str) -> Client {
+        let dir = std::env::temp_dir().join(format!("search-kube-{}-{}", std::process::id(), server.rsplit(':').next().unwrap()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("token"), "secret\n").unwrap();
+        let path = dir.join("config");
+        fs::write(&path, format!("apiVersion: v1
+clusters:
+- name: test
+  cluster:
+    server: {}
+contexts:
+- name: other
+  context:
+    cluster: missing
+- name: test
+  context:
+    cluster: test
+    user: test
+    namespace: shop
+current-context: test
+users:
+- name: test
+  user:
+    tokenFile: token
+", server)).unwrap();
+        let config = Kubeconfig::read(&path).unwrap();
+        assert_eq!(config.context_names(), vec!["other", "test"]);
+        assert!(Client::new(&config, Some("other")).is_err());
+        let client = Client::new(&config, None).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        client
+    }
+
+    #[test]
+    fn lists_pods_and_reports_api_errors() {
+        let pods = r#"{"items":[{"metadata":{"name":"web-1","labels":{"app":"web"}},"spec":{"containers":[{"name":"app"}]},"status":{"phase":"Running"}}]}"#;
+        let status = r#"{"kind":"Status","status":"Failure","reason":"Forbidden","message":"pods is forbidden","code":403}"#;
+        let (server, requests) = mock_server(vec![response("200 OK", pods), response("403 Forbidden", status)]);
+        let client = client(&server);
+        assert_eq!(client.namespace(), "shop");
+
+        let listed: Pods = client.get("/api/v1/namespaces/shop/pods", &[("labelSelector", "app=web")]).unwrap();
+        assert_eq!(listed.items[0].metadata.name, "web-1");
+        let request = requests.recv().unwrap();
+        assert!(request.starts_with("GET /api/v1/namespaces/shop/pods?labelSelector=app%3Dweb "));
+        assert!(request.contains("Authorization: Bearer secret\r\n"));
+
+        let error = client.get::<Pods>("/api/v1/namespaces/shop/pods", &[]).err().unwrap();
+        assert_eq!(error.to_string(), "403 Forbidden: pods is forbidden");
+        assert!(!error.is_not_found());
+    }
+
+    #[test]
+    fn streams_logs() {
+        let log = "2022-08-07T02:10:20.5Z one\n2022-08-07T02:10:21.5Z two\n";
+        let chunked = format!("HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{}\r\n0\r\n\r\n", log.len(), log);
+        let (server, requests) = mock_server(vec![chunked]);
+        let client = client(&server);
+
+        let (mut stream, _) = client.stream("/api/v1/namespaces/shop/pods/web-1/log", &[("follow", "true"), ("container", "app")]).unwrap();
+        let mut body = String::new();
+        stream.read_to_string(&mut body).unwrap();
+        assert_eq!(body, log);
+        assert!(requests.recv().unwrap().starts_with("GET /api/v1/namespaces/shop/pods/web-1/log?follow=true&container=app "));
+    }
+
+    #[test]
+    fn shuts_down_a_quiet_stream() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = client(&format!("http://{}", listener.local_addr().unwrap()));
+        let (done, wait) = channel::<()>();
+        spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let line = "2022-08-07T02:10:20.5Z one\n";
+            write!(stream, "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{}\r\n", line.len(), line).unwrap();
+            // Quiet until the test is over
+            let _ = wait.recv();
+        });
+
+        let (stream, handle) = client.stream("/api/v1/namespaces/shop/pods/web-1/log", &[("follow", "true")]).unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "2022-08-07T02:10:20.5Z one\n");
+        let reading = spawn(move || reader.read_line(&mut String::new()));
+        handle.shutdown();
+        assert!(!matches!(reading.join().unwrap(), Ok(n) if n > 0));
+        done.send(()).unwrap();
+    }
+}
@@ -1,3 +1,6 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize, Serialize)]
@@ -8,17 +11,79 @@ pub(crate) struct Pods {
 #[derive(Deserialize, Serialize)]
 pub(crate) struct Item {
     pub(crate) metadata: Metadata,
+    #[serde(default)]
+    pub(crate) spec: Spec,
     pub(crate) status: Status,
 }
 
 #[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub(crate) struct Metadata {
     pub(crate) name: String,
+    pub(crate) creation_timestamp: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub(crate) labels: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Spec {
+    #[serde(default)]
+    pub(crate) containers: Vec<Container>,
+    #[serde(default)]
+    pub(crate) init_containers: Vec<Container>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct Container {
+    pub(crate) name: String,
 }
 
 #[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub(crate) struct Status {
     pub(crate) phase: String,
+    #[serde(default)]
+    pub(crate) container_statuses: Vec<ContainerStatus>,
+    #[serde(default)]
+    pub(crate) init_container_statuses: Vec<ContainerStatus>,
+}
+
+impl Status {
+    pub(crate) fn container(&self, name: &str) -> Option<&ContainerStatus> {
+        self.init_container_statuses.iter().chain(self.container_statuses.iter()).find(|c| c.name == name)
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ContainerStatus {
+    pub(crate) name: String,
+    #[serde(default)]
+    pub(crate) restart_count: u32,
+    #[serde(default)]
+    pub(crate) state: ContainerState,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+pub(crate) struct ContainerState {
+    pub(crate) waiting: Option<Reason>,
+    pub(crate) terminated: Option<Reason>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct Reason {
+    #[serde(default)]
+    pub(crate) reason: String,
+}
+
+impl ContainerStatus {
+    /// The waiting or terminated reason, e.g. `CrashLoopBackOff`
+    pub(crate) fn reason(&self) -> Option<&str> {
+        self.state.waiting.as_ref().or(self.state.terminated.as_ref())
+            .map(|r| r.reason.as_str())
+            .filter(|r| !r.is_empty())
+    }
 }
 
 #[derive(Deserialize, Serialize)]
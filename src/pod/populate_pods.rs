@@ -1,16 +1,74 @@
 use std::process::{Command, Output};
 
+use chrono::{Duration, Utc};
+
 use crate::{App, pod, Pod, StatefulList};
+use crate::pod::cluster::{current_context, current_namespace, list_contexts, list_namespaces, list_pods};
+
+pub fn populate_contexts(app: &mut App) {
+    populate_names(app, list_contexts(), current_context());
+}
+
+pub fn populate_namespaces(app: &mut App) {
+    let current = app.target.namespace.clone().or_else(|| current_namespace(&app.target));
+    populate_names(app, list_namespaces(&app.target), current);
+}
+
+/// Fills the list with `names` and highlights the current one.
+fn populate_names(app: &mut App, names: Result<Vec<String>, String>, current: Option<String>) {
+    app.pods = StatefulList::with_items(vec![]);
+    match names {
+        Ok(names) => {
+            let current = names.iter().position(|n| Some(n) == current.as_ref());
+            app.pods = StatefulList::with_items(names.into_iter().map(Pod::named).collect());
+            app.pods.state.select(current.or(Some(0)));
+        }
+        Err(err) => {
+            app.error = Some(err);
+        }
+    }
+}
 
 pub fn populate_pods(app: &mut App) {
-    let output = Command::new("oc")
-        .arg("get")
-        .arg("pods")
-        .arg("-o")
-        .arg("json")
-        .output()
-        .expect("'oc get pods -o json' failed to start");
-    populate(app, &output);
+    app.pods = StatefulList::with_items(vec![]);
+    match list_pods(&app.target, None) {
+        Ok(pods) => {
+            let now = Utc::now();
+            app.pods = StatefulList::with_items(pods.items.iter()
+                .flat_map(|p| {
+                    let init = p.spec.init_containers.iter().map(|c| (c, true));
+                    init.chain(p.spec.containers.iter().map(|c| (c, false)))
+                        .map(|(c, init)| {
+                            let status = p.status.container(&c.name);
+                            let reason = status.and_then(|s| s.reason());
+                            Pod {
+                                name: p.metadata.name.clone(),
+                                labels: p.metadata.labels.clone(),
+                                container: Some(c.name.clone()),
+                                init,
+                                phase: reason.unwrap_or(&p.status.phase).to_string(),
+                                restarts: status.map(|s| s.restart_count).unwrap_or(0),
+                                age: p.metadata.creation_timestamp.map(|t| age(now - t)).unwrap_or_default(),
+                                crashloop: reason == Some("CrashLoopBackOff"),
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                }).collect());
+        }
+        Err(err) => {
+            app.error = Some(err);
+        }
+    }
+}
+
+/// Formats an age the way `oc get pods` does, e.g. `42s`, `5m`, `3h`, `12d`.
+fn age(age: Duration) -> String {
+    match age {
+        a if a.num_days() > 0 => { format!("{}d", a.num_days()) }
+        a if a.num_hours() > 0 => { format!("{}h", a.num_hours()) }
+        a if a.num_minutes() > 0 => { format!("{}m", a.num_minutes()) }
+        a => { format!("{}s", a.num_seconds().max(0)) }
+    }
 }
 
 pub fn populate_topics(app: &mut App) {
@@ -33,37 +91,15 @@ fn populate_kafka(app: &mut App, output: &Output) {
             let pods = match result {
                 Ok(l) => { l }
                 Err(err) => {
-                    println!("{}", err.to_string());
+                    app.error = Some(err.to_string());
                     return;
                 }
             };
             app.pods = StatefulList::with_items(pods.topics.iter()
-                .map(|p| { Pod { name: p.to_string() } }).collect());
-        }
-        false => {
-            println!("{}", String::from_utf8_lossy(&output.stderr));
-        }
-    }
-}
-
-fn populate(app: &mut App, output: &Output) {
-    app.pods = StatefulList::with_items(vec![]);
-    match output.status.success() {
-        true => {
-            let result: Result<pod::pods::Pods, _> = serde_json::from_str(String::from_utf8_lossy(&output.stdout).to_string().as_str());
-
-            let pods = match result {
-                Ok(l) => { l }
-                Err(err) => {
-                    println!("{}", err.to_string());
-                    return;
-                }
-            };
-            app.pods = StatefulList::with_items(pods.items.iter().filter(|pod| pod.status.phase == "Running")
-                .map(|p| { Pod { name: p.metadata.name.clone() } }).collect());
+                .map(|p| { Pod::named(p.to_string()) }).collect());
         }
         false => {
-            println!("{}", String::from_utf8_lossy(&output.stderr));
+            app.error = Some(String::from_utf8_lossy(&output.stderr).trim().to_string());
         }
     }
 }
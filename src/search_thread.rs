@@ -4,42 +4,71 @@ use std::thread;
 use std::time::Instant;
 
 use regex::Regex;
+use serde::Deserialize;
 
 use command_message::CommandMessage;
 use result_message::ResultMessage;
 
-use crate::search_thread::messages::Messages;
+use crate::search_thread::messages::{evict, Messages};
 
 pub mod command_message;
 pub mod result_message;
 mod messages;
 mod merge;
 
+/// `memory` in `search.json`, the oldest messages are dropped beyond it
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct Budget {
+    pub(crate) max_bytes: Option<u64>,
+    pub(crate) max_messages: Option<usize>,
+}
+
+impl Default for Budget {
+    fn default() -> Budget {
+        Budget { max_bytes: Some(1_000_000_000), max_messages: None }
+    }
+}
+
+impl Budget {
+    fn exceeded(&self, size: u64, count: usize) -> bool {
+        self.max_bytes.is_some_and(|max| size > max) || self.max_messages.is_some_and(|max| count > max)
+    }
+}
+
 struct Storage {
     filter: Regex,
     filter_not: Vec<Regex>,
+    filter_fields: Vec<(String, Regex)>,
     messages: Messages,
     skip_messages: Messages,
     skip: usize,
     result_size: usize,
+    budget: Budget,
+    /// Dropped for the budget since the last clear
+    evicted: usize,
 }
 
 impl Default for Storage {
     fn default() -> Storage {
         Storage {
-            filter: Regex::new(format!(r#"{}"#, ".*").as_str()).unwrap(),
+            filter: Regex::new(".*").unwrap(),
             filter_not: Vec::new(),
+            filter_fields: Vec::new(),
             messages: Messages::new(),
             skip_messages: Messages::new(),
             skip: 0,
             result_size: 0,
+            budget: Budget::default(),
+            evicted: 0,
         }
     }
 }
 
-pub fn search_thread(rx: Receiver<CommandMessage>, tx_result: Sender<ResultMessage>) {
+pub fn search_thread(rx: Receiver<CommandMessage>, tx_result: Sender<ResultMessage>, budget: Budget) {
     thread::spawn(move || {
-        let mut storage = Storage::default();
+        let mut storage = Storage { budget, ..Storage::default() };
+        let match_all = Regex::new(".*").unwrap();
         loop {
             let command_message =
                 match rx.try_recv() {
@@ -54,10 +83,10 @@ pub fn search_thread(rx: Receiver<CommandMessage>, tx_result: Sender<ResultMessa
                                     .messages
                                     .iter()
                                     .filter(|x| storage.filter.is_match(x.value.as_str()))
-                                    .filter(|x| if storage.filter_not.len() == 0 { true } else { !storage.filter_not.iter().any(|y| y.is_match(x.value.as_str())) })
+                                    .filter(|x| if storage.filter_not.is_empty() { true } else { !storage.filter_not.iter().any(|y| y.is_match(x.value.as_str())) })
+                                    .filter(|x| storage.filter_fields.iter().all(|(k, v)| x.fields.get(k).is_some_and(|f| v.is_match(f))))
                                     .skip(storage.skip)
-                                    .take(storage.result_size)
-                                    .map(|m| { m.clone() }).collect())).unwrap();
+                                    .take(storage.result_size).cloned().collect())).unwrap();
                                 tx_result.send(ResultMessage::Elapsed(now.elapsed())).unwrap();
                                 rx.recv().unwrap()
                             }
@@ -67,16 +96,24 @@ pub fn search_thread(rx: Receiver<CommandMessage>, tx_result: Sender<ResultMessa
                 };
             match command_message {
                 CommandMessage::FilterRegex(s) => {
-                    storage.filter = Regex::new(format!(r#".*{}.*"#, s).as_str()).unwrap_or(Regex::new(format!(r#"{}"#, ".*").as_str()).unwrap())
+                    storage.filter = Regex::new(format!(r#".*{}.*"#, s).as_str()).unwrap_or_else(|_| match_all.clone())
                 }
                 CommandMessage::Exit => {
                     break;
                 }
                 CommandMessage::InsertJson(message) => {
-                    if storage.skip == 0 {
-                        storage.messages.put(message);
-                    } else {
-                        storage.skip_messages.put(message);
+                    match storage.skip {
+                        0 => { storage.messages.put(message) }
+                        _ => { storage.skip_messages.put(message) }
+                    }
+                    let evicted = evict(&storage.budget, &mut storage.messages, &mut storage.skip_messages);
+                    if evicted > 0 {
+                        storage.evicted += evicted;
+                        let oldest = storage.messages.oldest().into_iter().chain(storage.skip_messages.oldest()).min();
+                        match tx_result.send(ResultMessage::Evicted(storage.evicted, oldest)) {
+                            Ok(_) => {}
+                            Err(_) => { return; }
+                        };
                     }
                     match tx_result.send(ResultMessage::Size(storage.messages.size + storage.skip_messages.size)) {
                         Ok(_) => {}
@@ -89,10 +126,10 @@ pub fn search_thread(rx: Receiver<CommandMessage>, tx_result: Sender<ResultMessa
                 }
                 CommandMessage::SetSkip(i) => {
                     if storage.skip == 1 && i == 0 {
-                        let x1: Vec<_> = storage.skip_messages.map.into_iter().map(|f| {
+                        let x1: Vec<_> = storage.skip_messages.map.into_iter().flat_map(|f| {
                             let x2: Vec<_> = f.1.into_iter().rev().collect();
                             x2
-                        }).flatten().collect();
+                        }).collect();
                         let len = x1.len();
                         x1.into_iter().for_each(|m| storage.messages.put(m));
                         storage.skip_messages = Messages::new();
@@ -115,6 +152,11 @@ pub fn search_thread(rx: Receiver<CommandMessage>, tx_result: Sender<ResultMessa
                     storage.messages.map = HashMap::new();
                     storage.messages.count = 0;
                     storage.messages.size = 0;
+                    storage.evicted = 0;
+                    match tx_result.send(ResultMessage::Evicted(0, None)) {
+                        Ok(_) => {}
+                        Err(_) => { return; }
+                    };
                     match tx_result.send(ResultMessage::Size(storage.messages.size)) {
                         Ok(_) => {}
                         Err(_) => { return; }
@@ -126,23 +168,52 @@ pub fn search_thread(rx: Receiver<CommandMessage>, tx_result: Sender<ResultMessa
                 }
                 CommandMessage::FilterNotRegexes(s) => {
                     let filters: Vec<_> = s.iter()
-                        .map(|v| Regex::new(format!(r#".*{}.*"#, v).as_str()).unwrap_or(Regex::new(format!(r#"{}"#, ".*").as_str()).unwrap()))
+                        .map(|v| Regex::new(format!(r#".*{}.*"#, v).as_str()).unwrap_or_else(|_| match_all.clone()))
                         .collect();
                     storage.filter_not = filters;
                 }
-                CommandMessage::ToggleInfo() => {
-                    storage.messages.info()
-                }
-                CommandMessage::ToggleDebug() => {
-                    storage.messages.debug()
-                }
-                CommandMessage::ToggleWarn() => {
-                    storage.messages.warn()
+                CommandMessage::FilterFields(s) => {
+                    storage.filter_fields = s.into_iter()
+                        .map(|(k, v)| (k, Regex::new(format!(r#"^(?:{})$"#, v).as_str()).unwrap_or_else(|_| match_all.clone())))
+                        .collect();
                 }
-                CommandMessage::ToggleError() => {
-                    storage.messages.error()
+                CommandMessage::ToggleLevel(level) => {
+                    storage.messages.toggle(level)
                 }
             }
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+
+    use chrono::{TimeZone, Utc};
+
+    use crate::search_thread::{Budget, search_thread};
+    use crate::search_thread::command_message::CommandMessage;
+    use crate::search_thread::messages::tests::message;
+    use crate::search_thread::result_message::ResultMessage;
+
+    #[test]
+    fn budget_counts_skipped_messages() {
+        let (tx, rx) = channel();
+        let (tx_result, rx_result) = channel();
+        [1, 2].iter().for_each(|s| tx.send(CommandMessage::InsertJson(message("shop", *s))).unwrap());
+        tx.send(CommandMessage::SetSkip(2)).unwrap();
+        [3, 4, 5].iter().for_each(|s| tx.send(CommandMessage::InsertJson(message("shop", *s))).unwrap());
+        tx.send(CommandMessage::Exit).unwrap();
+        search_thread(rx, tx_result, Budget { max_bytes: None, max_messages: Some(3) });
+
+        let mut evicted = None;
+        let mut length = None;
+        rx_result.iter().for_each(|r| match r {
+            ResultMessage::Evicted(n, oldest) => { evicted = Some((n, oldest)) }
+            ResultMessage::Length(n) => { length = Some(n) }
+            _ => {}
+        });
+        assert_eq!(evicted, Some((2, Some(Utc.ymd(2022, 8, 7).and_hms(2, 10, 3)))));
+        assert_eq!(length, Some(3));
+    }
+}
@@ -1,13 +1,12 @@
-use crate::Message;
+use crate::{Level, Message};
 
 pub enum CommandMessage {
     FilterRegex(String),
     FilterNotRegexes(Vec<String>),
+    /// Field name and a regex for its whole value
+    FilterFields(Vec<(String, String)>),
     InsertJson(Message),
-    ToggleInfo(),
-    ToggleDebug(),
-    ToggleWarn(),
-    ToggleError(),
+    ToggleLevel(Level),
     SetSkip(usize),
     SetResultSize(usize),
     Clear,
@@ -53,8 +53,8 @@ mod tests {
 
     #[test]
     fn find_value_in_text() {
-        let x1 = &vec![3, 2, 1];
-        let x2 = &vec![6, 5, 4];
+        let x1 = &[3, 2, 1];
+        let x2 = &[6, 5, 4];
 
 
         let ascending = MergeAscending::new(x1.iter(), x2.iter());
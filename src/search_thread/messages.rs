@@ -1,105 +1,148 @@
 use std::{iter, mem};
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+use chrono::{DateTime, Utc};
 
 use crate::{Level, Message};
+use crate::search_thread::Budget;
 use crate::search_thread::merge::MergeAscending;
 
 pub struct Messages {
     pub(crate) count: usize,
     pub(crate) size: u64,
-    pub(crate) map: HashMap<String, VecDeque<Message>>,
-    show_info: bool,
-    show_warn: bool,
-    show_debug: bool,
-    show_error: bool,
+    /// By level and `namespace/system/container`
+    pub(crate) map: HashMap<(Level, String), VecDeque<Message>>,
+    hidden: BTreeSet<Level>,
 }
 
 impl Messages {
     pub(crate) fn new() -> Messages {
-        Messages { count: 0, size: 0, map: HashMap::new(), show_info: true, show_warn: true, show_debug: true, show_error: true }
+        Messages { count: 0, size: 0, map: HashMap::new(), hidden: BTreeSet::new() }
     }
 
-    pub(crate) fn info(&mut self) -> () {
-        self.show_info = !self.show_info;
-    }
-    pub(crate) fn debug(&mut self) -> () {
-        self.show_debug = !self.show_debug;
-    }
-    pub(crate) fn warn(&mut self) -> () {
-        self.show_warn = !self.show_warn;
-    }
-    pub(crate) fn error(&mut self) -> () {
-        self.show_error = !self.show_error;
+    pub(crate) fn toggle(&mut self, level: Level) {
+        if !self.hidden.remove(&level) {
+            self.hidden.insert(level);
+        }
     }
 
     pub(crate) fn iter(&self) -> Box<dyn Iterator<Item=&Message> + '_> {
-        let x: Vec<&VecDeque<Message>> = self.map.iter().filter(|entry| {
-            if self.show_debug {
-                if entry.0.starts_with(Level::DEBUG.to_string().as_str()) {
-                    return true;
-                }
-            }
-            if self.show_error {
-                if entry.0.starts_with(Level::ERROR.to_string().as_str()) {
-                    return true;
-                }
-            }
-            if self.show_info {
-                if entry.0.starts_with(Level::INFO.to_string().as_str()) {
-                    return true;
-                }
-            }
-            if self.show_warn {
-                if entry.0.starts_with(Level::WARN.to_string().as_str()) {
-                    return true;
-                }
-            }
-            return false;
-        }).map(|entry| entry.1).collect::<Vec<_>>();
-        if x.len() == 0 {
+        let x: Vec<&VecDeque<Message>> = self.map.iter()
+            .filter(|entry| !self.hidden.contains(&entry.0.0))
+            .map(|entry| entry.1).collect::<Vec<_>>();
+        if x.is_empty() {
             return Box::new(iter::empty::<&Message>());
         }
 
-        let mut ma: Box<dyn Iterator<Item=_>> = Box::new(x[0].iter().map(|i| i));
+        let mut ma: Box<dyn Iterator<Item=_>> = Box::new(x[0].iter());
         for v in x.iter().skip(1) {
-            ma = Box::new(MergeAscending::new(ma, v.iter().map(|i| i)));
+            ma = Box::new(MergeAscending::new(ma, v.iter()));
         };
-        return ma;
+        ma
     }
 
     pub(crate) fn put(&mut self, m: Message) {
-        if self.size > 1_000_000_000 {
-            self.map.values_mut().for_each(|v| {
-                match v.pop_back() {
-                    None => {}
-                    Some(m) => {
-                        self.size -= m.value.len() as u64;
-                        self.size -= m.system.len() as u64;
-                        self.size -= mem::size_of_val(&m.timestamp) as u64;
-                        self.count -= 1;
-                    }
-                };
-            });
-            self.map.shrink_to_fit();
-        }
         self.count += 1;
-        self.size += m.value.len() as u64;
-        self.size += m.system.len() as u64;
-        self.size += mem::size_of_val(&m.timestamp) as u64;
-        let key = m.system.to_string();
-
-        let entries = self.map.entry(format!("{} {}", m.level.to_string(), key)).or_insert_with(|| VecDeque::new());
-        match entries.front() {
-            None => { entries.push_front(m); }
-            Some(front_message) => {
-                match m.timestamp >= front_message.timestamp {
-                    true => { entries.push_front(m); }
-                    false => {
-                        let idx = entries.partition_point(|x| x > &m);
-                        entries.insert(idx, m);
-                    }
-                }
+        self.size += size_of(&m);
+        let key = format!("{}/{}/{}", m.namespace, m.system, m.container);
+
+        let entries = self.map.entry((m.level, key)).or_default();
+        match (entries.front(), entries.back()) {
+            (Some(front_message), _) if m.timestamp >= front_message.timestamp => { entries.push_front(m); }
+            // Files read newest first, like `app.log` before `app.log.1`
+            (_, Some(back_message)) if m.timestamp <= back_message.timestamp => { entries.push_back(m); }
+            (None, _) => { entries.push_front(m); }
+            _ => {
+                let idx = entries.partition_point(|x| x > &m);
+                entries.insert(idx, m);
             }
         }
     }
+
+    /// Drops the oldest message, whatever its level and system.
+    fn pop_oldest(&mut self) {
+        let oldest = self.map.iter()
+            .filter_map(|(key, entries)| Some((entries.back()?.timestamp, key)))
+            .min_by_key(|(timestamp, _)| *timestamp)
+            .map(|(_, key)| key.clone());
+        let key = match oldest {
+            Some(key) => { key }
+            None => { return; }
+        };
+        let entries = self.map.get_mut(&key).unwrap();
+        if let Some(m) = entries.pop_back() {
+            self.size -= size_of(&m);
+            self.count -= 1;
+        }
+        if entries.is_empty() {
+            self.map.remove(&key);
+        }
+    }
+
+    /// The timestamp of the oldest message kept.
+    pub(crate) fn oldest(&self) -> Option<DateTime<Utc>> {
+        self.map.values().filter_map(|entries| entries.back()).map(|m| m.timestamp).min()
+    }
+}
+
+/// Drops the oldest messages of both stores until all they keep together fits the budget.
+/// Returns how many were dropped.
+pub(crate) fn evict(budget: &Budget, messages: &mut Messages, skip_messages: &mut Messages) -> usize {
+    let mut evicted = 0;
+    while budget.exceeded(messages.size + skip_messages.size, messages.count + skip_messages.count) {
+        let store = match (messages.oldest(), skip_messages.oldest()) {
+            (Some(kept), Some(skipped)) if skipped < kept => { &mut *skip_messages }
+            (Some(_), _) => { &mut *messages }
+            (None, Some(_)) => { &mut *skip_messages }
+            (None, None) => { break; }
+        };
+        store.pop_oldest();
+        evicted += 1;
+    }
+    evicted
+}
+
+/// What a message adds to the memory used.
+fn size_of(m: &Message) -> u64 {
+    let fields: usize = m.fields.iter().map(|(k, v)| k.len() + v.len()).sum();
+    let stack: usize = m.stack.iter().flat_map(|s| &s.exceptions)
+        .map(|e| e.class.len() + e.message.len() + e.frames.iter().map(|f| f.len()).sum::<usize>())
+        .sum();
+    (m.value.len() + m.system.len() + m.namespace.len() + m.container.len() + fields + stack + mem::size_of_val(&m.timestamp)) as u64
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use std::collections::BTreeMap;
+
+    use chrono::{TimeZone, Utc};
+
+    use crate::{Level, Message};
+    use crate::search_thread::Budget;
+    use crate::search_thread::messages::{evict, Messages};
+
+    /// A message of `system` at the given second, for the tests of the search thread
+    pub(crate) fn message(system: &str, second: u32) -> Message {
+        Message {
+            timestamp: Utc.ymd(2022, 8, 7).and_hms(2, 10, second),
+            system: system.to_string(),
+            namespace: String::new(),
+            container: String::new(),
+            level: Level::INFO,
+            value: "ok".to_string(),
+            fields: BTreeMap::new(),
+            stack: None,
+        }
+    }
+
+    #[test]
+    fn evicts_oldest_of_all_systems() {
+        let mut messages = Messages::new();
+        [("noisy", 1), ("quiet", 2), ("noisy", 3), ("noisy", 4), ("noisy", 5)].iter().for_each(|(s, t)| messages.put(message(s, *t)));
+        let budget = Budget { max_bytes: None, max_messages: Some(3) };
+        assert_eq!(evict(&budget, &mut messages, &mut Messages::new()), 2);
+        assert_eq!(messages.count, 3);
+        assert_eq!(messages.oldest(), Some(Utc.ymd(2022, 8, 7).and_hms(2, 10, 3)));
+        assert!(messages.map.keys().all(|(_, system)| system.contains("noisy")));
+    }
 }
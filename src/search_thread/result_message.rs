@@ -1,5 +1,7 @@
 use std::time::Duration;
 
+use chrono::{DateTime, Utc};
+
 use crate::Message;
 
 pub enum ResultMessage {
@@ -8,4 +10,6 @@ pub enum ResultMessage {
     Size(u64),
     Length(usize),
     Skip(usize),
+    /// How many messages were dropped for the memory budget and the oldest one kept
+    Evicted(usize, Option<DateTime<Utc>>),
 }
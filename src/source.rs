@@ -0,0 +1,368 @@
+use std::fmt;
+use std::io;
+use std::io::{BufRead, ErrorKind, Read};
+use std::sync::{Arc, Mutex, RwLock};
+use std::mem;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+
+use crate::{CommandMessage, OtherOrdering};
+use crate::detect::{Candidates, SAMPLE_SIZE};
+use crate::diagnostics::{Diagnostics, Reject};
+use crate::mapping::FieldMapping;
+use crate::multiline::{Assembler, Multiline};
+use crate::parse_send::{Event, Origin, Parser};
+use crate::args::{Args, SourceOptions};
+use crate::config::Config;
+use crate::source::watch::{WatchSource, WatchTarget};
+
+pub mod command;
+pub mod dedup;
+pub mod file;
+pub mod file_set;
+pub mod pod;
+pub mod stdin;
+pub mod watch;
+
+#[derive(PartialEq, Eq, Clone)]
+pub enum SourceStatus {
+    Starting,
+    Running,
+    Paused,
+    Reconnecting,
+    Stopped,
+    Ended,
+    Failed(String),
+}
+
+impl fmt::Display for SourceStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SourceStatus::Starting => { write!(f, "starting") }
+            SourceStatus::Running => { write!(f, "running") }
+            SourceStatus::Paused => { write!(f, "paused") }
+            SourceStatus::Reconnecting => { write!(f, "reconnecting") }
+            SourceStatus::Stopped => { write!(f, "stopped") }
+            SourceStatus::Ended => { write!(f, "ended") }
+            SourceStatus::Failed(e) => { write!(f, "failed: {}", e) }
+        }
+    }
+}
+
+/// How far a source reading a set of files got, `read` and `total` are bytes on disk
+#[derive(Clone, Copy)]
+pub struct Progress {
+    pub(crate) file: usize,
+    pub(crate) files: usize,
+    pub(crate) read: u64,
+    pub(crate) total: u64,
+}
+
+/// State shared between a source and the threads reading it
+pub struct SourceState {
+    stop: AtomicBool,
+    paused: AtomicBool,
+    lines: AtomicUsize,
+    bytes: AtomicU64,
+    status: Mutex<SourceStatus>,
+    mapping: RwLock<Arc<FieldMapping>>,
+    parser: RwLock<Arc<Parser>>,
+    /// The first events while the parser is `auto`
+    samples: Mutex<Vec<Event>>,
+    /// Whether `auto` picked the parser
+    detected: AtomicBool,
+    multiline: RwLock<Arc<Multiline>>,
+    diagnostics: Mutex<Diagnostics>,
+    progress: Mutex<Option<Progress>>,
+}
+
+impl SourceState {
+    pub fn new() -> Arc<SourceState> {
+        Arc::new(SourceState {
+            stop: AtomicBool::new(false),
+            paused: AtomicBool::new(false),
+            lines: AtomicUsize::new(0),
+            bytes: AtomicU64::new(0),
+            status: Mutex::new(SourceStatus::Stopped),
+            mapping: RwLock::new(Arc::new(FieldMapping::builtin().remove(0))),
+            parser: RwLock::new(Arc::new(Parser::Auto(Arc::new(Candidates::default())))),
+            samples: Mutex::new(Vec::new()),
+            detected: AtomicBool::new(false),
+            multiline: RwLock::new(Arc::new(Multiline::default())),
+            diagnostics: Mutex::new(Diagnostics::default()),
+            progress: Mutex::new(None),
+        })
+    }
+
+    pub fn should_stop(&self) -> bool {
+        self.stop.load(OtherOrdering::SeqCst)
+    }
+
+    pub fn status(&self) -> SourceStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    pub fn set_status(&self, status: SourceStatus) {
+        *self.status.lock().unwrap() = status;
+    }
+
+    /// The profile lines are parsed with, can be changed while the source runs.
+    pub fn mapping(&self) -> Arc<FieldMapping> {
+        self.mapping.read().unwrap().clone()
+    }
+
+    pub fn set_mapping(&self, mapping: Arc<FieldMapping>) {
+        *self.mapping.write().unwrap() = mapping;
+    }
+
+    pub fn parser(&self) -> Arc<Parser> {
+        self.parser.read().unwrap().clone()
+    }
+
+    pub fn set_parser(&self, parser: Arc<Parser>) {
+        *self.parser.write().unwrap() = parser;
+        self.detected.store(false, OtherOrdering::SeqCst);
+    }
+
+    /// Holds an event back on `auto`, returns all of them once there are enough to pick a parser.
+    pub fn sample(&self, event: Event) -> Option<Vec<Event>> {
+        let mut samples = self.samples.lock().unwrap();
+        samples.push(event);
+        match samples.len() >= SAMPLE_SIZE {
+            true => { Some(mem::take(&mut *samples)) }
+            false => { None }
+        }
+    }
+
+    pub fn take_samples(&self) -> Vec<Event> {
+        mem::take(&mut *self.samples.lock().unwrap())
+    }
+
+    /// Replaces `auto` with the parser, and for JSON the profile, it picked.
+    pub fn set_detected(&self, parser: Parser, mapping: Option<Arc<FieldMapping>>) {
+        self.set_parser(Arc::new(parser));
+        if let Some(mapping) = mapping {
+            self.set_mapping(mapping);
+        }
+        self.detected.store(true, OtherOrdering::SeqCst);
+    }
+
+    pub fn detected(&self) -> bool {
+        self.detected.load(OtherOrdering::SeqCst)
+    }
+
+    /// How lines are joined into events, read when the source starts.
+    pub fn multiline(&self) -> Arc<Multiline> {
+        self.multiline.read().unwrap().clone()
+    }
+
+    pub fn set_multiline(&self, multiline: Arc<Multiline>) {
+        *self.multiline.write().unwrap() = multiline;
+    }
+
+    /// Counts a line that couldn't be parsed.
+    pub fn reject(&self, reason: Reject, line: &str) {
+        self.diagnostics.lock().unwrap().add(reason, line);
+    }
+
+    pub fn diagnostics(&self) -> Diagnostics {
+        self.diagnostics.lock().unwrap().clone()
+    }
+
+    /// `None` unless the source reads a set of files one after the other.
+    pub fn progress(&self) -> Option<Progress> {
+        *self.progress.lock().unwrap()
+    }
+
+    pub fn set_progress(&self, progress: Option<Progress>) {
+        *self.progress.lock().unwrap() = progress;
+    }
+
+    fn advance(&self, bytes: u64) {
+        if let Some(p) = self.progress.lock().unwrap().as_mut() {
+            p.read += bytes;
+        }
+    }
+
+    pub fn lines(&self) -> usize {
+        self.lines.load(OtherOrdering::SeqCst)
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(OtherOrdering::SeqCst)
+    }
+
+    fn count(&self, line: &str) {
+        self.lines.fetch_add(1, OtherOrdering::SeqCst);
+        self.bytes.fetch_add(line.len() as u64, OtherOrdering::SeqCst);
+    }
+
+    fn wait_while_paused(&self) {
+        while self.paused.load(OtherOrdering::SeqCst) && !self.should_stop() {
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+}
+
+/// Something that produces log lines, started and stopped from the UI
+pub trait LogSource: Send {
+    fn name(&self) -> &str;
+    fn kind(&self) -> &str;
+    fn state(&self) -> &Arc<SourceState>;
+    fn start(&mut self, sender: Sender<CommandMessage>);
+    /// Signals the reading threads to stop and waits for them.
+    fn stop(&mut self);
+
+    fn restart(&mut self, sender: Sender<CommandMessage>) {
+        self.stop();
+        self.start(sender);
+    }
+
+    /// False for sources that can't be read again once stopped, like stdin.
+    fn restartable(&self) -> bool {
+        true
+    }
+
+    /// Whether picking pods or topics in the UI replaces this source.
+    fn picked(&self) -> bool {
+        false
+    }
+
+    fn pause(&mut self) {
+        if self.status() == SourceStatus::Running {
+            self.state().paused.store(true, OtherOrdering::SeqCst);
+            self.state().set_status(SourceStatus::Paused);
+        }
+    }
+
+    fn resume(&mut self) {
+        if self.status() == SourceStatus::Paused {
+            self.state().paused.store(false, OtherOrdering::SeqCst);
+            self.state().set_status(SourceStatus::Running);
+        }
+    }
+
+    fn status(&self) -> SourceStatus {
+        self.state().status()
+    }
+
+    fn set_mapping(&mut self, mapping: Arc<FieldMapping>) {
+        self.state().set_mapping(mapping);
+    }
+
+    fn set_parser(&mut self, parser: Arc<Parser>) {
+        self.state().set_parser(parser);
+    }
+
+    /// Sources started by this one, like the pods of a followed deployment.
+    fn children(&self) -> Vec<ChildSource> {
+        Vec::new()
+    }
+}
+
+/// What the sources view shows of a source started by another one
+pub struct ChildSource {
+    pub(crate) name: String,
+    pub(crate) kind: String,
+    pub(crate) state: Arc<SourceState>,
+}
+
+impl ChildSource {
+    pub fn of<S: LogSource>(source: &S) -> ChildSource {
+        ChildSource {
+            name: source.name().to_string(),
+            kind: source.kind().to_string(),
+            state: source.state().clone(),
+        }
+    }
+}
+
+/// Resets the shared state before a source starts its threads.
+pub(crate) fn begin(state: &SourceState) {
+    state.stop.store(false, OtherOrdering::SeqCst);
+    state.paused.store(false, OtherOrdering::SeqCst);
+    state.set_status(SourceStatus::Starting);
+}
+
+/// Signals the threads of a source to stop.
+pub(crate) fn signal_stop(state: &SourceState) {
+    state.stop.store(true, OtherOrdering::SeqCst);
+}
+
+/// Sends every event of `reader` to the search thread until it ends or the source is stopped.
+pub(crate) fn read_to_end<R: BufRead>(reader: R, origin: &Origin, state: &Arc<SourceState>, sender: &Sender<CommandMessage>) -> io::Result<()> {
+    let assembler = Assembler::start(origin, state, sender);
+    let read = read_lines(reader, state, |line| assembler.push(line));
+    assembler.finish();
+    read
+}
+
+/// Passes over the first `n` lines, the ones a source read before it was restarted,
+/// without counting them again. Returns how many there were.
+pub(crate) fn skip_lines<R: BufRead>(mut reader: R, n: usize) -> usize {
+    let mut buf = Vec::new();
+    let mut skipped = 0;
+    while skipped < n {
+        match reader.read_until(b'\n', &mut buf) {
+            Ok(0) | Err(_) => { break; }
+            Ok(_) => {
+                skipped += 1;
+                buf.clear();
+            }
+        }
+    }
+    skipped
+}
+
+/// Hands every line of `reader` to `f` until it ends or the source is stopped, bytes
+/// that aren't UTF-8 replaced. An error reading is returned unless the source was
+/// stopped, which can end the read with one.
+pub(crate) fn read_lines<R: BufRead, F: FnMut(&str)>(mut reader: R, state: &SourceState, mut f: F) -> io::Result<()> {
+    let mut buf = Vec::new();
+    while !state.should_stop() {
+        state.wait_while_paused();
+        match reader.read_until(b'\n', &mut buf) {
+            Ok(0) => { return Ok(()); }
+            Ok(_) => {
+                let line = String::from_utf8_lossy(&buf);
+                state.count(&line);
+                f(&line);
+                buf.clear()
+            }
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(_) if state.should_stop() => { return Ok(()); }
+            Err(e) => { return Err(e); }
+        }
+    }
+    Ok(())
+}
+
+/// Sources given on the command line and piped into stdin.
+pub fn sources_from_args(args: &Args, config: &Config, input: Option<Box<dyn Read + Send>>) -> Result<Vec<Box<dyn LogSource>>, String> {
+    let mut sources: Vec<(Box<dyn LogSource>, &SourceOptions)> = Vec::new();
+    args.files.iter().for_each(|(path, o)| sources.push((Box::new(file::FileSource::new(path)), o)));
+    args.file_sets.iter().for_each(|(set, o)| sources.push((Box::new(file_set::FileSetSource::new(set.clone())), o)));
+    args.selectors.iter().for_each(|(s, o)| sources.push((Box::new(WatchSource::new(WatchTarget::Selector(s.clone()), &args.target)), o)));
+    args.deployments.iter().for_each(|(d, o)| sources.push((Box::new(WatchSource::new(WatchTarget::Deployment(d.clone()), &args.target)), o)));
+    if let Some(input) = input {
+        sources.push((Box::new(stdin::StdinSource::new(input)), &args.options));
+    }
+    sources.into_iter()
+        .map(|(mut source, options)| {
+            configure(source.as_mut(), options, config)?;
+            Ok(source)
+        })
+        .collect()
+}
+
+/// Applies the command line options and the configured patterns to a source before it starts.
+pub fn configure(source: &mut dyn LogSource, options: &SourceOptions, config: &Config) -> Result<(), String> {
+    if let Some(profile) = &options.profile {
+        source.set_mapping(config.profile(profile)?);
+    }
+    source.set_parser(Arc::new(config.parser(options.parser.as_deref().unwrap_or("auto"), options.profile.as_deref())?));
+    source.state().set_multiline(config.multiline.clone());
+    Ok(())
+}
@@ -0,0 +1,99 @@
+use std::io::BufReader;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::thread::{JoinHandle, spawn};
+use std::time::Duration;
+
+use crate::CommandMessage;
+use crate::parse_send::Origin;
+use crate::source::{begin, LogSource, read_to_end, signal_stop, SourceState, SourceStatus};
+
+/// Reads the stdout of a child process, like the kafka tool
+pub struct CommandSource {
+    name: String,
+    kind: &'static str,
+    program: String,
+    args: Vec<String>,
+    state: Arc<SourceState>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl CommandSource {
+    pub fn new(name: &str, kind: &'static str, program: &str, args: Vec<String>) -> CommandSource {
+        CommandSource {
+            name: name.to_string(),
+            kind,
+            program: program.to_string(),
+            args,
+            state: SourceState::new(),
+            handle: None,
+        }
+    }
+
+    pub fn kafka(topics: &str) -> CommandSource {
+        CommandSource::new(topics, "kafka", "java", vec!["-jar".to_string(), "kafka.jar".to_string(), "topic".to_string(), topics.to_string()])
+    }
+}
+
+impl LogSource for CommandSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn kind(&self) -> &str {
+        self.kind
+    }
+
+    fn state(&self) -> &Arc<SourceState> {
+        &self.state
+    }
+
+    fn picked(&self) -> bool {
+        true
+    }
+
+    fn start(&mut self, sender: Sender<CommandMessage>) {
+        begin(&self.state);
+        let origin = Origin::new(&self.name);
+        let state = self.state.clone();
+        let mut command = Command::new(&self.program);
+        command.args(&self.args).stdout(Stdio::piped()).stderr(Stdio::null());
+        self.handle = Some(spawn(move || {
+            let mut child = match command.spawn() {
+                Ok(c) => { c }
+                Err(e) => {
+                    state.set_status(SourceStatus::Failed(e.to_string()));
+                    return;
+                }
+            };
+            let reader = child.stdout.take().map(|stdout| {
+                state.set_status(SourceStatus::Running);
+                let reader_state = state.clone();
+                spawn(move || read_to_end(BufReader::new(stdout), &origin, &reader_state, &sender))
+            });
+            while !state.should_stop() {
+                if let Ok(Some(_)) = child.try_wait() {
+                    // The rest of its output is still in the pipe
+                    state.set_status(match reader.map(|r| r.join()) {
+                        Some(Ok(Err(e))) => { SourceStatus::Failed(e.to_string()) }
+                        _ => { SourceStatus::Ended }
+                    });
+                    return;
+                }
+                thread::sleep(Duration::from_millis(100));
+            }
+            let _ = child.kill();
+            let _ = child.wait();
+            state.set_status(SourceStatus::Stopped);
+        }));
+    }
+
+    fn stop(&mut self) {
+        signal_stop(&self.state);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+
+use chrono::{DateTime, Utc};
+
+/// Drops the lines a reconnect with `--since-time` sends again. The stream is
+/// ordered, so anything older than the last seen timestamp was seen already and
+/// lines with exactly that timestamp are compared by content.
+#[derive(Default)]
+pub struct Dedup {
+    last: Option<DateTime<Utc>>,
+    seen: HashSet<u64>,
+}
+
+impl Dedup {
+    pub fn last(&self) -> Option<DateTime<Utc>> {
+        self.last
+    }
+
+    pub fn is_new(&mut self, timestamp: DateTime<Utc>, line: &str) -> bool {
+        let mut hasher = DefaultHasher::new();
+        line.hash(&mut hasher);
+        let hash = hasher.finish();
+        match self.last {
+            Some(last) if timestamp < last => { false }
+            Some(last) if timestamp == last => { self.seen.insert(hash) }
+            _ => {
+                self.last = Some(timestamp);
+                self.seen.clear();
+                self.seen.insert(hash);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+
+    use crate::source::dedup::Dedup;
+
+    fn ts(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().into()
+    }
+
+    #[test]
+    fn drops_lines_repeated_after_reconnect() {
+        let mut dedup = Dedup::default();
+        assert!(dedup.is_new(ts("2022-08-07T04:10:20.5Z"), "a"));
+        assert!(dedup.is_new(ts("2022-08-07T04:10:21.1Z"), "b"));
+        assert!(dedup.is_new(ts("2022-08-07T04:10:21.1Z"), "c"));
+
+        // reconnected with --since-time=2022-08-07T04:10:21Z
+        assert!(!dedup.is_new(ts("2022-08-07T04:10:20.5Z"), "a"));
+        assert!(!dedup.is_new(ts("2022-08-07T04:10:21.1Z"), "b"));
+        assert!(!dedup.is_new(ts("2022-08-07T04:10:21.1Z"), "c"));
+        assert!(dedup.is_new(ts("2022-08-07T04:10:21.1Z"), "d"));
+        assert!(dedup.is_new(ts("2022-08-07T04:10:22Z"), "b"));
+    }
+}
@@ -0,0 +1,222 @@
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, ErrorKind, Read};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::AtomicUsize;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::thread::{JoinHandle, spawn};
+use std::time::Duration;
+
+use flate2::read::MultiGzDecoder;
+use ruzstd::decoding::{BlockDecodingStrategy, FrameDecoder};
+use ruzstd::decoding::errors::{FrameDecoderError, ReadFrameHeaderError};
+
+use crate::{CommandMessage, OtherOrdering};
+use crate::multiline::Assembler;
+use crate::parse_send::Origin;
+use crate::source::{begin, LogSource, read_lines, signal_stop, skip_lines, SourceState, SourceStatus};
+use crate::tail::FileTail;
+
+/// Follows a file on disk, the file name is the system of lines without `application`.
+/// gzip and zstd files are read once to the end. A restart goes on where the source stopped.
+pub struct FileSource {
+    name: String,
+    path: String,
+    state: Arc<SourceState>,
+    handle: Option<JoinHandle<()>>,
+    tail: Arc<Mutex<FileTail>>,
+    /// Lines read of a compressed file
+    consumed: Arc<AtomicUsize>,
+}
+
+impl FileSource {
+    pub fn new(path: &str) -> FileSource {
+        let name = Path::new(path).file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_else(|| path.to_string());
+        FileSource {
+            name,
+            path: path.to_string(),
+            state: SourceState::new(),
+            handle: None,
+            tail: Arc::new(Mutex::new(FileTail::new(path))),
+            consumed: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+}
+
+impl LogSource for FileSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn kind(&self) -> &str {
+        "file"
+    }
+
+    fn state(&self) -> &Arc<SourceState> {
+        &self.state
+    }
+
+    fn start(&mut self, sender: Sender<CommandMessage>) {
+        begin(&self.state);
+        let origin = Origin::new(&self.name);
+        let state = self.state.clone();
+        let path = self.path.clone();
+        let tail = self.tail.clone();
+        let consumed = self.consumed.clone();
+        self.handle = Some(spawn(move || {
+            state.set_status(SourceStatus::Running);
+            let file = match File::open(&path) {
+                Ok(f) => { Some(f) }
+                Err(e) if e.kind() == ErrorKind::NotFound => { None }
+                Err(e) => {
+                    state.set_status(SourceStatus::Failed(e.to_string()));
+                    return;
+                }
+            };
+            if let Some(file) = file {
+                match decompress(BufReader::new(file)) {
+                    Ok((reader, true)) => {
+                        let mut reader = BufReader::new(reader);
+                        skip_lines(&mut reader, consumed.load(OtherOrdering::SeqCst));
+                        let assembler = Assembler::start(&origin, &state, &sender);
+                        let read = read_lines(reader, &state, |line| {
+                            consumed.fetch_add(1, OtherOrdering::SeqCst);
+                            assembler.push(line);
+                        });
+                        assembler.finish();
+                        state.set_status(match read {
+                            Err(e) => { SourceStatus::Failed(e.to_string()) }
+                            Ok(_) if state.should_stop() => { SourceStatus::Stopped }
+                            Ok(_) => { SourceStatus::Ended }
+                        });
+                        return;
+                    }
+                    Ok((_, false)) => {}
+                    Err(e) => {
+                        state.set_status(SourceStatus::Failed(e.to_string()));
+                        return;
+                    }
+                }
+            }
+            follow(&mut tail.lock().unwrap(), &origin, &state, &sender);
+        }));
+    }
+
+    fn stop(&mut self) {
+        signal_stop(&self.state);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Reads a file like `tail -F` until the source is stopped, from where `tail` left off.
+pub(crate) fn follow(tail: &mut FileTail, origin: &Origin, state: &Arc<SourceState>, sender: &Sender<CommandMessage>) {
+    let assembler = Assembler::start(origin, state, sender);
+    let mut buf = String::new();
+    while !state.should_stop() {
+        state.wait_while_paused();
+        match tail.read_line(&mut buf) {
+            Ok(0) => {
+                thread::sleep(Duration::from_millis(100));
+            }
+            Ok(_) => {
+                state.count(&buf);
+                assembler.push(&buf);
+                buf.clear()
+            }
+            Err(e) => {
+                assembler.finish();
+                state.set_status(SourceStatus::Failed(e.to_string()));
+                return;
+            }
+        }
+    }
+    state.set_status(SourceStatus::Stopped);
+}
+
+/// The decompressed content when it starts with the magic bytes of gzip or zstd, with
+/// `true`, otherwise the content as it is.
+pub(crate) fn decompress<R: Read + Send + 'static>(mut reader: BufReader<R>) -> io::Result<(Box<dyn Read + Send>, bool)> {
+    let magic = reader.fill_buf()?;
+    match magic {
+        [0x1f, 0x8b, ..] => { Ok((Box::new(MultiGzDecoder::new(reader)), true)) }
+        [0x28, 0xb5, 0x2f, 0xfd, ..] => { Ok((Box::new(ZstdFrames { source: reader, frame: FrameDecoder::new() }), true)) }
+        _ => { Ok((Box::new(reader), false)) }
+    }
+}
+
+/// Reads every frame of a zstd file, concatenated files have more than one. Skippable
+/// frames are passed over, a frame that can't be decoded is an error.
+struct ZstdFrames<R: BufRead> {
+    source: R,
+    frame: FrameDecoder,
+}
+
+impl<R: BufRead> Read for ZstdFrames<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let invalid = |e: FrameDecoderError| io::Error::new(ErrorKind::InvalidData, e.to_string());
+        loop {
+            while self.frame.can_collect() < buf.len() && !self.frame.is_finished() {
+                let needed = buf.len() - self.frame.can_collect();
+                self.frame.decode_blocks(&mut self.source, BlockDecodingStrategy::UptoBytes(needed)).map_err(invalid)?;
+            }
+            let read = self.frame.read(buf)?;
+            if read > 0 || buf.is_empty() {
+                return Ok(read);
+            }
+            // The next frame, there is none at the end of the file
+            if self.source.fill_buf()?.is_empty() {
+                return Ok(0);
+            }
+            match self.frame.reset(&mut self.source) {
+                Ok(_) => {}
+                Err(FrameDecoderError::ReadFrameHeaderError(ReadFrameHeaderError::SkipFrame { length, .. })) => {
+                    io::copy(&mut (&mut self.source).take(length as u64), &mut io::sink())?;
+                }
+                Err(e) => { return Err(invalid(e)); }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Read, Write};
+
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use ruzstd::encoding::{compress_to_vec, CompressionLevel};
+
+    use crate::source::file::decompress;
+
+    fn read(bytes: Vec<u8>) -> (String, bool) {
+        let (mut reader, compressed) = decompress(BufReader::new(std::io::Cursor::new(bytes))).unwrap();
+        let mut text = String::new();
+        reader.read_to_string(&mut text).unwrap();
+        (text, compressed)
+    }
+
+    fn gzip(text: &str) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn decompresses_every_member_and_frame() {
+        assert_eq!(read([gzip("one\n"), gzip("two\n")].concat()), ("one\ntwo\n".to_string(), true));
+
+        let skippable = [&[0x50, 0x2a, 0x4d, 0x18, 3, 0, 0, 0][..], b"abc"].concat();
+        let frames = [compress_to_vec(&b"one\n"[..], CompressionLevel::Fastest), skippable, compress_to_vec(&b"two\n"[..], CompressionLevel::Fastest)].concat();
+        assert_eq!(read(frames.clone()), ("one\ntwo\n".to_string(), true));
+
+        let corrupt = [frames, vec![0x28, 0xb5, 0x2f, 0xfd, 0xff]].concat();
+        let (mut reader, _) = decompress(BufReader::new(std::io::Cursor::new(corrupt))).unwrap();
+        assert!(reader.read_to_string(&mut String::new()).is_err());
+
+        assert_eq!(read(b"plain\n".to_vec()), ("plain\n".to_string(), false));
+    }
+}
@@ -0,0 +1,270 @@
+use std::cmp::Reverse;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::AtomicUsize;
+use std::sync::mpsc::Sender;
+use std::thread::{JoinHandle, spawn};
+use std::time::SystemTime;
+
+use glob::Pattern;
+
+use crate::{CommandMessage, OtherOrdering};
+use crate::multiline::Assembler;
+use crate::parse_send::Origin;
+use crate::source::{begin, LogSource, Progress, read_lines, signal_stop, skip_lines, SourceState, SourceStatus};
+use crate::source::file::{decompress, follow};
+use crate::tail::FileTail;
+
+/// Files read one after the other, oldest first
+#[derive(Clone)]
+pub enum FileSet {
+    /// `app.log` with `app.log.1`, `app.log.2.gz` and so on, or `app.log-20220807` of `dateext`
+    Rotated(String),
+    /// Ordered by modification time
+    Glob(String),
+}
+
+/// Where a file of a rotated set is in the rotation, newest last
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum Rotation {
+    Dated(String),
+    Numbered(Reverse<u32>),
+    Current,
+}
+
+impl FileSet {
+    /// The files of the set, oldest first. The current file of a rotated set is in it
+    /// even when it doesn't exist yet.
+    pub fn files(&self) -> Result<Vec<PathBuf>, String> {
+        match self {
+            FileSet::Rotated(path) => {
+                let mut files: Vec<(Rotation, PathBuf)> = matches(&format!("{}*", Pattern::escape(path)))?.into_iter()
+                    .filter_map(|f| Some((rotation(path, &f)?, f)))
+                    .collect();
+                if !files.iter().any(|(r, _)| *r == Rotation::Current) {
+                    files.push((Rotation::Current, PathBuf::from(path)));
+                }
+                files.sort();
+                Ok(files.into_iter().map(|(_, f)| f).collect())
+            }
+            FileSet::Glob(pattern) => {
+                let mut files: Vec<(SystemTime, PathBuf)> = matches(pattern)?.into_iter()
+                    .map(|f| (f.metadata().and_then(|m| m.modified()).unwrap_or(SystemTime::UNIX_EPOCH), f))
+                    .collect();
+                if files.is_empty() {
+                    return Err(format!("no file matches {}", pattern));
+                }
+                files.sort();
+                Ok(files.into_iter().map(|(_, f)| f).collect())
+            }
+        }
+    }
+}
+
+fn matches(pattern: &str) -> Result<Vec<PathBuf>, String> {
+    let paths = glob::glob(pattern).map_err(|e| format!("{}: {}", pattern, e))?;
+    Ok(paths.filter_map(|p| p.ok()).filter(|p| p.is_file()).collect())
+}
+
+/// `None` for files that only share the prefix, like `app.log.bak`. The names are compared
+/// as glob leaves out a leading `./` of the path.
+fn rotation(path: &str, file: &Path) -> Option<Rotation> {
+    let current = Path::new(path).file_name()?.to_string_lossy();
+    let name = file.file_name()?.to_string_lossy();
+    let suffix = name.strip_prefix(current.as_ref())?;
+    let suffix = suffix.trim_end_matches(".gz").trim_end_matches(".zst");
+    if suffix.is_empty() {
+        return Some(Rotation::Current);
+    }
+    if let Some(n) = suffix.strip_prefix('.').and_then(|n| n.parse().ok()) {
+        return Some(Rotation::Numbered(Reverse(n)));
+    }
+    match suffix.strip_prefix('-') {
+        Some(date) if !date.is_empty() && date.chars().all(|c| c.is_ascii_digit()) => { Some(Rotation::Dated(date.to_string())) }
+        _ => { None }
+    }
+}
+
+/// Reads a set of rotated or matching files oldest first, decompressing the
+/// compressed ones, and follows the newest one unless it is compressed. A restart
+/// goes on where the source stopped.
+pub struct FileSetSource {
+    name: String,
+    set: FileSet,
+    state: Arc<SourceState>,
+    handle: Option<JoinHandle<()>>,
+    /// The newest file once it is followed
+    tail: Arc<Mutex<Option<FileTail>>>,
+    /// Lines read of the files before it
+    consumed: Arc<AtomicUsize>,
+}
+
+impl FileSetSource {
+    pub fn new(set: FileSet) -> FileSetSource {
+        let name = match &set {
+            FileSet::Rotated(path) => { Path::new(path).file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_else(|| path.to_string()) }
+            FileSet::Glob(pattern) => { pattern.clone() }
+        };
+        FileSetSource {
+            name,
+            set,
+            state: SourceState::new(),
+            handle: None,
+            tail: Arc::new(Mutex::new(None)),
+            consumed: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+}
+
+impl LogSource for FileSetSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn kind(&self) -> &str {
+        "files"
+    }
+
+    fn state(&self) -> &Arc<SourceState> {
+        &self.state
+    }
+
+    fn start(&mut self, sender: Sender<CommandMessage>) {
+        begin(&self.state);
+        let origin = Origin::new(&self.name);
+        let state = self.state.clone();
+        let set = self.set.clone();
+        let tail = self.tail.clone();
+        let consumed = self.consumed.clone();
+        self.handle = Some(spawn(move || {
+            let follow_newest = |path: &Path| {
+                let mut tail = tail.lock().unwrap();
+                follow(tail.get_or_insert_with(|| FileTail::new(path)), &origin, &state, &sender);
+            };
+            state.set_status(SourceStatus::Running);
+            let files = match set.files() {
+                Ok(f) => { f }
+                Err(e) => {
+                    state.set_status(SourceStatus::Failed(e));
+                    return;
+                }
+            };
+            let total = files.iter().filter_map(|f| f.metadata().ok()).map(|m| m.len()).sum();
+            state.set_progress(None);
+            let assembler = Assembler::start(&origin, &state, &sender);
+            let mut skip = consumed.load(OtherOrdering::SeqCst);
+            for (i, path) in files.iter().enumerate() {
+                if state.should_stop() {
+                    break;
+                }
+                let read = state.progress().map(|p| p.read).unwrap_or_default();
+                state.set_progress(Some(Progress { file: i + 1, files: files.len(), read, total }));
+                let newest = i + 1 == files.len();
+                let file = match File::open(path) {
+                    Ok(f) => { f }
+                    Err(e) if e.kind() == ErrorKind::NotFound && newest => {
+                        assembler.finish();
+                        state.set_progress(None);
+                        follow_newest(path);
+                        return;
+                    }
+                    Err(e) => {
+                        assembler.finish();
+                        state.set_status(SourceStatus::Failed(format!("{}: {}", path.display(), e)));
+                        return;
+                    }
+                };
+                let counting = Counting { inner: file, state: state.clone() };
+                match decompress(BufReader::new(counting)) {
+                    Ok((_, false)) if newest => {
+                        assembler.finish();
+                        state.set_progress(None);
+                        follow_newest(path);
+                        return;
+                    }
+                    Ok((reader, _)) => {
+                        let mut reader = BufReader::new(reader);
+                        skip -= skip_lines(&mut reader, skip);
+                        let read = read_lines(reader, &state, |line| {
+                            consumed.fetch_add(1, OtherOrdering::SeqCst);
+                            assembler.push(line);
+                        });
+                        if let Err(e) = read {
+                            assembler.finish();
+                            state.set_status(SourceStatus::Failed(format!("{}: {}", path.display(), e)));
+                            return;
+                        }
+                    }
+                    Err(e) => {
+                        assembler.finish();
+                        state.set_status(SourceStatus::Failed(format!("{}: {}", path.display(), e)));
+                        return;
+                    }
+                }
+            }
+            assembler.finish();
+            state.set_progress(None);
+            state.set_status(match state.should_stop() {
+                true => { SourceStatus::Stopped }
+                false => { SourceStatus::Ended }
+            });
+        }));
+    }
+
+    fn stop(&mut self) {
+        signal_stop(&self.state);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Counts the bytes read from disk into the progress of the source
+struct Counting<R: Read> {
+    inner: R,
+    state: Arc<SourceState>,
+}
+
+impl<R: Read> Read for Counting<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.state.advance(read as u64);
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Reverse;
+    use std::fs;
+    use std::path::Path;
+
+    use crate::source::file_set::{FileSet, Rotation, rotation};
+
+    #[test]
+    fn orders_rotated_files() {
+        let mut files: Vec<_> = ["app.log", "app.log.2.gz", "app.log.10.zst", "app.log.1", "app.log-20220807"].iter()
+            .filter_map(|f| Some((rotation("app.log", Path::new(f))?, *f)))
+            .collect();
+        files.sort();
+        assert_eq!(files.iter().map(|(_, f)| *f).collect::<Vec<_>>(), ["app.log-20220807", "app.log.10.zst", "app.log.2.gz", "app.log.1", "app.log"]);
+        assert!(rotation("app.log", Path::new("app.log.bak")).is_none());
+        assert!(rotation("app.log", Path::new("app.log.3")) == Some(Rotation::Numbered(Reverse(3))));
+    }
+
+    #[test]
+    fn lists_rotated_files_of_a_relative_path() {
+        let dir = format!("./target/search-files-{}", std::process::id());
+        fs::create_dir_all(&dir).unwrap();
+        ["app.log", "app.log.1", "app.log.2.gz", "app.log.bak"].iter().for_each(|f| fs::write(Path::new(&dir).join(f), "").unwrap());
+
+        let files = FileSet::Rotated(format!("{}/app.log", dir)).files().unwrap();
+        let names: Vec<_> = files.iter().map(|f| f.file_name().unwrap().to_string_lossy().to_string()).collect();
+        assert_eq!(names, ["app.log.2.gz", "app.log.1", "app.log"]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
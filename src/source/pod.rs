@@ -0,0 +1,203 @@
+use std::io::BufReader;
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::thread::{JoinHandle, spawn};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use crate::CommandMessage;
+use crate::multiline::Assembler;
+use crate::parse_send::Origin;
+use crate::pod::cluster::{KubeTarget, LogOptions, pod_logs};
+use crate::pod::kube::KubeError;
+use crate::source::{begin, LogSource, read_lines, signal_stop, SourceState, SourceStatus};
+use crate::source::dedup::Dedup;
+
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// How far back the first connect reads
+const SINCE: Duration = Duration::from_secs(200 * 60 * 60);
+
+/// Follows the logs of a pod container and reconnects when the stream ends,
+/// continuing from the last seen line. A `previous` source reads the logs of the
+/// last terminated instance of the container once instead.
+pub struct PodSource {
+    name: String,
+    pod: String,
+    container: Option<String>,
+    previous: bool,
+    target: KubeTarget,
+    state: Arc<SourceState>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl PodSource {
+    pub fn new(pod: &str, container: Option<&str>, target: &KubeTarget) -> PodSource {
+        PodSource {
+            name: match container {
+                Some(c) => { format!("{}/{}", pod, c) }
+                None => { pod.to_string() }
+            },
+            pod: pod.to_string(),
+            container: container.map(|c| c.to_string()),
+            previous: false,
+            target: target.clone(),
+            state: SourceState::new(),
+            handle: None,
+        }
+    }
+
+    /// Reads what the previous instance of a restarted container logged, `previous=true`.
+    pub fn previous(pod: &str, container: Option<&str>, target: &KubeTarget) -> PodSource {
+        let mut source = PodSource::new(pod, container, target);
+        source.name = format!("{} (previous)", source.name);
+        source.previous = true;
+        source
+    }
+}
+
+impl LogSource for PodSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn kind(&self) -> &str {
+        "pod"
+    }
+
+    fn state(&self) -> &Arc<SourceState> {
+        &self.state
+    }
+
+    fn picked(&self) -> bool {
+        true
+    }
+
+    fn start(&mut self, sender: Sender<CommandMessage>) {
+        begin(&self.state);
+        let pod = self.pod.clone();
+        let container = self.container.clone();
+        let previous = self.previous;
+        let target = self.target.clone();
+        let state = self.state.clone();
+        self.handle = Some(spawn(move || {
+            let origin = Origin {
+                system: pod.clone(),
+                namespace: target.namespace.clone().unwrap_or_default(),
+                container: container.clone().unwrap_or_default(),
+            };
+            if previous {
+                let status = match follow(&pod, &target, &origin, Dedup::default(), true, &state, &sender) {
+                    Ok(_) if state.should_stop() => { SourceStatus::Stopped }
+                    Ok(_) => { SourceStatus::Ended }
+                    Err((_, e)) => { SourceStatus::Failed(e.to_string()) }
+                };
+                state.set_status(status);
+                return;
+            }
+            let mut dedup = Dedup::default();
+            let mut backoff = Duration::from_secs(1);
+            while !state.should_stop() {
+                let last = dedup.last();
+                dedup = match follow(&pod, &target, &origin, dedup, false, &state, &sender) {
+                    Ok(d) => {
+                        state.set_status(SourceStatus::Reconnecting);
+                        d
+                    }
+                    Err((_, e)) if e.is_not_found() => {
+                        // The pod is gone, there is nothing to reconnect to
+                        state.set_status(SourceStatus::Ended);
+                        return;
+                    }
+                    Err((d, e)) => {
+                        state.set_status(SourceStatus::Failed(e.to_string()));
+                        d
+                    }
+                };
+                // Only back off while reconnects don't bring anything new
+                backoff = match dedup.last() != last {
+                    true => { Duration::from_secs(1) }
+                    false => { (backoff * 2).min(MAX_BACKOFF) }
+                };
+                let mut waited = Duration::ZERO;
+                while waited < backoff && !state.should_stop() {
+                    thread::sleep(Duration::from_millis(100));
+                    waited += Duration::from_millis(100);
+                }
+            }
+            state.set_status(SourceStatus::Stopped);
+        }));
+    }
+
+    fn stop(&mut self) {
+        signal_stop(&self.state);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Streams the log until it ends or the source is stopped, for `previous` until
+/// the last terminated instance's log has been read.
+fn follow(pod: &str, target: &KubeTarget, origin: &Origin, dedup: Dedup, previous: bool, state: &Arc<SourceState>, sender: &Sender<CommandMessage>) -> Result<Dedup, (Dedup, KubeError)> {
+    let options = LogOptions {
+        container: Some(origin.container.clone()).filter(|c| !c.is_empty()),
+        follow: !previous,
+        previous,
+        since_time: dedup.last().filter(|_| !previous),
+        since_seconds: match dedup.last().is_none() && !previous {
+            true => { Some(SINCE.as_secs() as i64) }
+            false => { None }
+        },
+    };
+    let (stream, handle) = match pod_logs(target, pod, &options) {
+        Ok(s) => { s }
+        Err(e) => { return Err((dedup, e)); }
+    };
+    state.set_status(SourceStatus::Running);
+    let reader_state = state.clone();
+    let origin = origin.clone();
+    let sender = sender.clone();
+    // Outside the reader, so the lines seen so far aren't lost when it panics
+    let dedup = Arc::new(Mutex::new(dedup));
+    let reader_dedup = dedup.clone();
+    let reader = spawn(move || {
+        let assembler = Assembler::start(&origin, &reader_state, &sender);
+        let read = read_lines(BufReader::new(stream), &reader_state, |line| {
+            let (timestamp, line) = split_timestamp(line);
+            match timestamp {
+                Some(t) if !reader_dedup.lock().unwrap().is_new(t, line) => {}
+                _ => { assembler.push(line) }
+            }
+        });
+        assembler.finish();
+        read
+    });
+    while !state.should_stop() && !reader.is_finished() {
+        thread::sleep(Duration::from_millis(100));
+    }
+    // Ends a read blocked on a quiet stream
+    handle.shutdown();
+    let read = reader.join();
+    let dedup = mem::take(&mut *dedup.lock().unwrap_or_else(|e| e.into_inner()));
+    match read {
+        Ok(Ok(_)) => { Ok(dedup) }
+        Ok(Err(e)) => { Err((dedup, KubeError::Transport(e.to_string()))) }
+        Err(_) => { Err((dedup, KubeError::Transport("reading the log failed".to_string()))) }
+    }
+}
+
+/// Splits the timestamp `timestamps=true` puts in front of every line.
+fn split_timestamp(line: &str) -> (Option<DateTime<Utc>>, &str) {
+    match line.split_once(' ') {
+        Some((prefix, rest)) => {
+            match DateTime::parse_from_rfc3339(prefix) {
+                Ok(t) => { (Some(t.with_timezone(&Utc)), rest) }
+                Err(_) => { (None, line) }
+            }
+        }
+        None => { (None, line) }
+    }
+}
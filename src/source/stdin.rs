@@ -0,0 +1,115 @@
+use std::io;
+use std::io::{BufReader, Read};
+use std::sync::Arc;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::thread::{JoinHandle, spawn};
+use std::time::Duration;
+
+use crossterm::tty::IsTty;
+
+use crate::CommandMessage;
+use crate::parse_send::Origin;
+use crate::source::{begin, LogSource, read_to_end, signal_stop, SourceState, SourceStatus};
+
+/// Lines piped into stdin, can only be read once so a restart just resumes it
+pub struct StdinSource {
+    input: Option<Box<dyn Read + Send>>,
+    state: Arc<SourceState>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl StdinSource {
+    pub fn new(input: Box<dyn Read + Send>) -> StdinSource {
+        StdinSource {
+            input: Some(input),
+            state: SourceState::new(),
+            handle: None,
+        }
+    }
+}
+
+impl LogSource for StdinSource {
+    fn name(&self) -> &str {
+        "stdin"
+    }
+
+    fn kind(&self) -> &str {
+        "stdin"
+    }
+
+    fn state(&self) -> &Arc<SourceState> {
+        &self.state
+    }
+
+    fn start(&mut self, sender: Sender<CommandMessage>) {
+        let input = match self.input.take() {
+            Some(i) => { i }
+            None => { return; }
+        };
+        begin(&self.state);
+        let state = self.state.clone();
+        self.handle = Some(spawn(move || {
+            state.set_status(SourceStatus::Running);
+            // Reads block until the pipe has data, so only this thread waits for the stop signal
+            let reader_state = state.clone();
+            let reader = spawn(move || read_to_end(BufReader::new(input), &Origin::new("stdin"), &reader_state, &sender));
+            while !state.should_stop() {
+                if reader.is_finished() {
+                    state.set_status(match reader.join() {
+                        Ok(Err(e)) => { SourceStatus::Failed(e.to_string()) }
+                        _ => { SourceStatus::Ended }
+                    });
+                    return;
+                }
+                thread::sleep(Duration::from_millis(100));
+            }
+            state.set_status(SourceStatus::Stopped);
+        }));
+    }
+
+    fn stop(&mut self) {
+        signal_stop(&self.state);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+
+    /// The pipe is gone once it was read.
+    fn restartable(&self) -> bool {
+        self.input.is_some()
+    }
+}
+
+/// Returns stdin when it is piped so it can be read as a log source. On unix
+/// the pipe is moved to a new descriptor and stdin is pointed at the
+/// controlling terminal, so keyboard and mouse events and any child process
+/// see the terminal instead of the log stream.
+pub fn take_piped_stdin() -> io::Result<Option<Box<dyn Read + Send>>> {
+    if io::stdin().is_tty() {
+        return Ok(None);
+    }
+    redirect_stdin_to_tty()
+}
+
+#[cfg(unix)]
+fn redirect_stdin_to_tty() -> io::Result<Option<Box<dyn Read + Send>>> {
+    use std::fs::{File, OpenOptions};
+    use std::os::unix::io::{AsRawFd, FromRawFd};
+
+    let piped = unsafe { libc::dup(libc::STDIN_FILENO) };
+    if piped < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let piped = unsafe { File::from_raw_fd(piped) };
+    let tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+    if unsafe { libc::dup2(tty.as_raw_fd(), libc::STDIN_FILENO) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(Some(Box::new(piped)))
+}
+
+#[cfg(not(unix))]
+fn redirect_stdin_to_tty() -> io::Result<Option<Box<dyn Read + Send>>> {
+    Ok(Some(Box::new(io::stdin())))
+}
@@ -0,0 +1,212 @@
+use std::collections::{BTreeMap, HashSet};
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::thread::{JoinHandle, spawn};
+use std::time::Duration;
+
+use crate::CommandMessage;
+use crate::mapping::FieldMapping;
+use crate::parse_send::Parser;
+use crate::pod::cluster::{deployment_selector, KubeTarget, list_pods};
+use crate::source::{begin, ChildSource, LogSource, signal_stop, SourceState, SourceStatus};
+use crate::source::pod::PodSource;
+
+const RELIST_INTERVAL: Duration = Duration::from_secs(10);
+
+pub enum WatchTarget {
+    Selector(String),
+    Deployment(String),
+}
+
+/// Follows every container of the running pods matching a label selector or
+/// deployment. Pods are listed again periodically so new replicas are picked up
+/// after a rollout and containers that went away are marked as ended.
+pub struct WatchSource {
+    name: String,
+    target: Arc<WatchTarget>,
+    kube: KubeTarget,
+    state: Arc<SourceState>,
+    pods: Arc<Mutex<Vec<PodSource>>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl WatchSource {
+    pub fn new(target: WatchTarget, kube: &KubeTarget) -> WatchSource {
+        let name = match &target {
+            WatchTarget::Selector(s) => { s.clone() }
+            WatchTarget::Deployment(d) => { format!("deployment/{}", d) }
+        };
+        WatchSource {
+            name,
+            target: Arc::new(target),
+            kube: kube.clone(),
+            state: SourceState::new(),
+            pods: Arc::new(Mutex::new(Vec::new())),
+            handle: None,
+        }
+    }
+}
+
+impl LogSource for WatchSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn kind(&self) -> &str {
+        "watch"
+    }
+
+    fn state(&self) -> &Arc<SourceState> {
+        &self.state
+    }
+
+    fn picked(&self) -> bool {
+        true
+    }
+
+    fn start(&mut self, sender: Sender<CommandMessage>) {
+        begin(&self.state);
+        let state = self.state.clone();
+        let target = self.target.clone();
+        let kube = self.kube.clone();
+        let pods = self.pods.clone();
+        pods.lock().unwrap().clear();
+        self.handle = Some(spawn(move || {
+            // Looked up once so the pods are tagged with the namespace they run in
+            let kube = kube.resolved();
+            while !state.should_stop() {
+                let listed = match target.as_ref() {
+                    WatchTarget::Selector(s) => { Ok(s.clone()) }
+                    WatchTarget::Deployment(d) => { deployment_selector(&kube, d) }
+                }.and_then(|selector| list_pods(&kube, Some(&selector)));
+                match listed {
+                    Ok(listed) => {
+                        let running: BTreeMap<String, (String, String)> = listed.items.into_iter()
+                            .filter(|p| p.status.phase == "Running")
+                            .flat_map(|p| {
+                                let pod = p.metadata.name;
+                                p.spec.containers.into_iter()
+                                    .map(move |c| (format!("{}/{}", pod, c.name), (pod.clone(), c.name)))
+                            })
+                            .collect();
+                        sync(&pods, &running, &kube, &state, &sender);
+                        if state.status() != SourceStatus::Paused {
+                            state.set_status(SourceStatus::Running);
+                        }
+                    }
+                    Err(e) => { state.set_status(SourceStatus::Failed(e)) }
+                }
+                let mut waited = Duration::ZERO;
+                while waited < RELIST_INTERVAL && !state.should_stop() {
+                    thread::sleep(Duration::from_millis(100));
+                    waited += Duration::from_millis(100);
+                }
+            }
+            let mut stopping = mem::take(&mut *pods.lock().unwrap());
+            stopping.iter_mut().for_each(|p| p.stop());
+            pods.lock().unwrap().extend(stopping);
+            state.set_status(SourceStatus::Stopped);
+        }));
+    }
+
+    fn stop(&mut self) {
+        signal_stop(&self.state);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+
+    fn pause(&mut self) {
+        if self.status() == SourceStatus::Running {
+            self.state.set_status(SourceStatus::Paused);
+            self.pods.lock().unwrap().iter_mut().for_each(|p| p.pause());
+        }
+    }
+
+    fn resume(&mut self) {
+        if self.status() == SourceStatus::Paused {
+            self.state.set_status(SourceStatus::Running);
+            self.pods.lock().unwrap().iter_mut().for_each(|p| p.resume());
+        }
+    }
+
+    fn set_mapping(&mut self, mapping: Arc<FieldMapping>) {
+        self.state.set_mapping(mapping.clone());
+        self.pods.lock().unwrap().iter_mut().for_each(|p| p.set_mapping(mapping.clone()));
+    }
+
+    fn set_parser(&mut self, parser: Arc<Parser>) {
+        self.state.set_parser(parser.clone());
+        self.pods.lock().unwrap().iter_mut().for_each(|p| p.set_parser(parser.clone()));
+    }
+
+    fn children(&self) -> Vec<ChildSource> {
+        self.pods.lock().unwrap().iter().map(ChildSource::of).collect()
+    }
+}
+
+/// Starts the containers that are new and ends the ones no longer running, which stay to
+/// be shown as ended. Stopping waits for the pod threads, so it happens outside the lock
+/// the sources view takes too.
+fn sync(pods: &Mutex<Vec<PodSource>>, running: &BTreeMap<String, (String, String)>, kube: &KubeTarget, state: &SourceState, sender: &Sender<CommandMessage>) {
+    let mut gone: Vec<PodSource> = {
+        let mut pods = pods.lock().unwrap();
+        let (gone, kept): (Vec<_>, Vec<_>) = mem::take(&mut *pods).into_iter()
+            .partition(|p| p.status() != SourceStatus::Ended && !running.contains_key(p.name()));
+        *pods = kept;
+        let known: HashSet<String> = pods.iter().chain(&gone).map(|p| p.name().to_string()).collect();
+        running.iter().filter(|(name, _)| !known.contains(*name)).for_each(|(_, (pod, container))| {
+            let mut pod = PodSource::new(pod, Some(container), kube);
+            pod.set_mapping(state.mapping());
+            pod.set_parser(state.parser());
+            pod.state().set_multiline(state.multiline());
+            pod.start(sender.clone());
+            if state.status() == SourceStatus::Paused {
+                pod.pause();
+            }
+            pods.push(pod);
+        });
+        gone
+    };
+    gone.iter_mut().for_each(|p| {
+        p.stop();
+        p.state().set_status(SourceStatus::Ended);
+    });
+    pods.lock().unwrap().extend(gone);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::sync::Mutex;
+    use std::sync::mpsc::channel;
+
+    use crate::pod::cluster::KubeTarget;
+    use crate::source::{LogSource, SourceState, SourceStatus};
+    use crate::source::pod::PodSource;
+    use crate::source::watch::sync;
+
+    #[test]
+    fn ends_containers_no_longer_running() {
+        let kube = KubeTarget::default();
+        let pods = Mutex::new(vec![PodSource::new("web-1", Some("app"), &kube), PodSource::new("web-2", Some("app"), &kube)]);
+        let (sender, _receiver) = channel();
+        let state = SourceState::new();
+        let running = |names: &[&str]| -> BTreeMap<String, (String, String)> {
+            names.iter().map(|n| (format!("{}/app", n), (n.to_string(), "app".to_string()))).collect()
+        };
+
+        sync(&pods, &running(&["web-1"]), &kube, &state, &sender);
+        let status = |pods: &Mutex<Vec<PodSource>>| -> Vec<(String, bool)> {
+            let mut status: Vec<_> = pods.lock().unwrap().iter().map(|p| (p.name().to_string(), p.status() == SourceStatus::Ended)).collect();
+            status.sort();
+            status
+        };
+        assert_eq!(status(&pods), [("web-1/app".to_string(), false), ("web-2/app".to_string(), true)]);
+
+        sync(&pods, &running(&[]), &kube, &state, &sender);
+        assert_eq!(status(&pods), [("web-1/app".to_string(), true), ("web-2/app".to_string(), true)]);
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::Message;
+
+/// Frames of these packages are hidden with CTRL-g
+const FRAMEWORKS: [&str; 11] = [
+    "java.base/", "java.", "javax.", "jdk.", "sun.", "kotlin.", "kotlinx.",
+    "org.springframework.", "org.apache.", "io.netty.", "reactor.",
+];
+
+/// A Java or Kotlin stack trace at the end of a message
+#[derive(PartialEq, Eq, Clone)]
+pub struct StackTrace {
+    /// Where the trace starts in the value of the message
+    pub(crate) start: usize,
+    /// The thrown exception first, then its causes
+    pub(crate) exceptions: Vec<Exception>,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum Link {
+    Thrown,
+    CausedBy,
+    Suppressed,
+}
+
+#[derive(PartialEq, Eq, Clone)]
+pub struct Exception {
+    pub(crate) link: Link,
+    pub(crate) class: String,
+    pub(crate) message: String,
+    /// Like `com.example.Shop.buy(Shop.java:42)`
+    pub(crate) frames: Vec<String>,
+    /// The frames `... 3 more` left out because the exception before has them
+    pub(crate) more: usize,
+}
+
+impl Exception {
+    fn header(&self) -> String {
+        let link = match self.link {
+            Link::Thrown => { "" }
+            Link::CausedBy => { "Caused by: " }
+            Link::Suppressed => { "Suppressed: " }
+        };
+        match self.message.is_empty() {
+            true => { format!("{}{}", link, self.class) }
+            false => { format!("{}{}: {}", link, self.class, self.message) }
+        }
+    }
+}
+
+fn exception_class() -> &'static Regex {
+    static CLASS: OnceLock<Regex> = OnceLock::new();
+    CLASS.get_or_init(|| Regex::new(r"(?:^|\s)((?:[a-zA-Z_$][\w$]*\.)+[A-Z][\w$]*(?:Exception|Error|Throwable))(?::|$)").unwrap())
+}
+
+/// The exception a line ends with. The line is scanned from the right, as the text
+/// before the exception may name classes too, but a class right after the `: ` of
+/// another one is the message of the exception that wraps it.
+fn exception(link: Link, line: &str) -> Option<(usize, Exception)> {
+    let classes: Vec<_> = exception_class().captures_iter(line).filter_map(|c| c.get(1)).collect();
+    let class = classes.iter().enumerate().rev()
+        .find(|(i, c)| *i == 0 || line[classes[i - 1].end()..c.start()].trim() != ":")
+        .map(|(_, c)| c)?;
+    let message = line[class.end()..].strip_prefix(':').map(|m| m.strip_prefix(' ').unwrap_or(m)).unwrap_or_default();
+    Some((class.start(), Exception {
+        link,
+        class: class.as_str().to_string(),
+        message: message.to_string(),
+        frames: Vec::new(),
+        more: 0,
+    }))
+}
+
+/// Finds the stack trace in a message, `None` when there is none or lines follow it
+/// that are not part of it.
+pub fn parse(value: &str) -> Option<StackTrace> {
+    let lines: Vec<(usize, &str)> = value.split('\n')
+        .scan(0, |offset, line| {
+            let start = *offset;
+            *offset += line.len() + 1;
+            Some((start, line))
+        })
+        .collect();
+    let is_frame = |line: &str| line.trim_start().starts_with("at ");
+    let mut start = None;
+    let mut exceptions: Vec<Exception> = Vec::new();
+    for (i, (offset, line)) in lines.iter().enumerate() {
+        let trimmed = line.trim_start();
+        match exceptions.last_mut() {
+            None => {
+                if lines.get(i + 1).is_some_and(|(_, next)| is_frame(next)) {
+                    if let Some((at, e)) = exception(Link::Thrown, line) {
+                        start = Some(offset + at);
+                        exceptions.push(e);
+                    }
+                }
+            }
+            Some(e) => {
+                if let Some(frame) = trimmed.strip_prefix("at ") {
+                    e.frames.push(frame.to_string());
+                } else if let Some(more) = trimmed.strip_prefix("... ").and_then(|m| m.strip_suffix(" more")) {
+                    e.more = more.parse().ok()?;
+                } else if let Some(rest) = trimmed.strip_prefix("Caused by: ") {
+                    exceptions.push(exception(Link::CausedBy, rest)?.1);
+                } else if let Some(rest) = trimmed.strip_prefix("Suppressed: ") {
+                    exceptions.push(exception(Link::Suppressed, rest)?.1);
+                } else if !trimmed.is_empty() {
+                    return None;
+                }
+            }
+        }
+    }
+    Some(StackTrace { start: start?, exceptions })
+}
+
+pub fn is_framework(frame: &str) -> bool {
+    FRAMEWORKS.iter().any(|p| frame.starts_with(p))
+}
+
+/// How stack traces are shown: collapsed to their exceptions unless expanded, with or
+/// without the frames of frameworks.
+#[derive(Default)]
+pub struct StackView {
+    /// Messages whose frames are shown, or hidden when `expand_all`
+    toggled: HashSet<u64>,
+    pub(crate) expand_all: bool,
+    pub(crate) hide_framework: bool,
+}
+
+impl StackView {
+    pub fn toggle(&mut self, key: u64) {
+        if !self.toggled.remove(&key) {
+            self.toggled.insert(key);
+        }
+    }
+
+    pub fn toggle_all(&mut self) {
+        self.expand_all = !self.expand_all;
+        self.toggled.clear();
+    }
+
+    /// The text of a message as it is shown.
+    pub fn text(&self, m: &Message) -> String {
+        let stack = match &m.stack {
+            Some(s) => { s }
+            None => { return m.value.clone(); }
+        };
+        let expanded = self.expand_all != self.toggled.contains(&key(m));
+        let mut text = m.value[..stack.start].to_string();
+        for (i, e) in stack.exceptions.iter().enumerate() {
+            if i > 0 {
+                text.push('\n');
+            }
+            text.push_str(&e.header());
+            if !expanded {
+                text.push_str(&format!(" [{} frames]", e.frames.len() + e.more));
+                continue;
+            }
+            let mut hidden = 0;
+            for frame in &e.frames {
+                match self.hide_framework && is_framework(frame) {
+                    true => { hidden += 1; }
+                    false => {
+                        if hidden > 0 {
+                            text.push_str(&format!("\n\t... {} framework frames", hidden));
+                            hidden = 0;
+                        }
+                        text.push_str(&format!("\n\tat {}", frame));
+                    }
+                }
+            }
+            if hidden > 0 {
+                text.push_str(&format!("\n\t... {} framework frames", hidden));
+            }
+            if e.more > 0 {
+                text.push_str(&format!("\n\t... {} more", e.more));
+            }
+        }
+        text
+    }
+}
+
+/// Tells messages apart across searches, they have no id.
+pub fn key(m: &Message) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (&m.timestamp, &m.system, &m.namespace, &m.container, &m.value).hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stack::{Link, parse};
+
+    #[test]
+    fn parses_causes_and_frames() {
+        let trace = parse("failed java.lang.IllegalStateException: boom\n\tat com.example.Shop.buy(Shop.java:42)\n\tat org.springframework.aop.Proxy.invoke(Proxy.java:7)\nCaused by: java.io.IOException: gone\n\tat com.example.Io.read(Io.java:3)\n\t... 2 more").unwrap();
+        assert_eq!(trace.start, 7);
+        assert_eq!(trace.exceptions.len(), 2);
+        assert_eq!(trace.exceptions[0].class, "java.lang.IllegalStateException");
+        assert_eq!(trace.exceptions[0].message, "boom");
+        assert_eq!(trace.exceptions[0].frames, ["com.example.Shop.buy(Shop.java:42)", "org.springframework.aop.Proxy.invoke(Proxy.java:7)"]);
+        assert!(trace.exceptions[1].link == Link::CausedBy);
+        assert_eq!(trace.exceptions[1].more, 2);
+        assert!(parse("java.lang.IllegalStateException: boom\n\tat com.example.Shop.buy(Shop.java:42)\nshutting down").is_none());
+        assert!(parse("no trace here").is_none());
+        let trace = parse("order com.example.Order failed java.lang.IllegalStateException: boom\n\tat com.example.Shop.buy(Shop.java:42)").unwrap();
+        assert_eq!(trace.start, 31);
+        assert_eq!(trace.exceptions[0].class, "java.lang.IllegalStateException");
+        let trace = parse("com.example.OrderError: retried java.lang.RuntimeException: java.io.IOException: gone\n\tat com.example.Io.read(Io.java:3)").unwrap();
+        assert_eq!(trace.exceptions[0].class, "java.lang.RuntimeException");
+        assert_eq!(trace.exceptions[0].message, "java.io.IOException: gone");
+    }
+}
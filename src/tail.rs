@@ -0,0 +1,167 @@
+use std::collections::VecDeque;
+use std::fs::{File, Metadata};
+use std::io;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Follows a file like `tail -F`: reads it from the start, keeps reading as it
+/// grows, starts over when it is truncated and reopens it when it is rotated.
+pub struct FileTail {
+    path: PathBuf,
+    reader: Option<BufReader<File>>,
+    identity: Option<FileIdentity>,
+    position: u64,
+    pending: Vec<u8>,
+    backlog: VecDeque<String>,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+struct FileIdentity(u64, u64);
+
+impl FileTail {
+    pub fn new<P: AsRef<Path>>(path: P) -> FileTail {
+        FileTail {
+            path: path.as_ref().to_path_buf(),
+            reader: None,
+            identity: None,
+            position: 0,
+            pending: Vec::new(),
+            backlog: VecDeque::new(),
+        }
+    }
+
+    /// Appends the next complete line to `buf`. Returns 0 when there is
+    /// nothing new yet, a trailing line without newline is held back until it
+    /// is finished. Bytes that aren't UTF-8 are replaced, not dropped.
+    pub fn read_line(&mut self, buf: &mut String) -> io::Result<usize> {
+        if let Some(line) = self.backlog.pop_front() {
+            buf.push_str(&line);
+            return Ok(line.len());
+        }
+        if self.reader.is_none() && !self.open()? {
+            return Ok(0);
+        }
+        let reader = self.reader.as_mut().unwrap();
+        let read = reader.read_until(b'\n', &mut self.pending)?;
+        self.position += read as u64;
+        if self.pending.ends_with(b"\n") {
+            let line = String::from_utf8_lossy(&self.pending).to_string();
+            buf.push_str(&line);
+            self.pending.clear();
+            return Ok(line.len());
+        }
+        if self.check_rotation()? {
+            return self.read_line(buf);
+        }
+        Ok(0)
+    }
+
+    fn open(&mut self) -> io::Result<bool> {
+        let file = match File::open(&self.path) {
+            Ok(f) => { f }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => { return Ok(false); }
+            Err(e) => { return Err(e); }
+        };
+        self.identity = file_identity(&file.metadata()?);
+        self.reader = Some(BufReader::new(file));
+        self.position = 0;
+        self.pending.clear();
+        Ok(true)
+    }
+
+    /// Returns true when the file was reopened or rewound and should be read again.
+    fn check_rotation(&mut self) -> io::Result<bool> {
+        let metadata = match std::fs::metadata(&self.path) {
+            Ok(m) => { m }
+            // Moved away and not recreated yet, keep the old handle until it is
+            Err(_) => { return Ok(false); }
+        };
+        if file_identity(&metadata) != self.identity {
+            self.drain()?;
+            return self.open();
+        }
+        if metadata.len() < self.position {
+            if let Some(reader) = self.reader.as_mut() {
+                reader.seek(SeekFrom::Start(0))?;
+            }
+            self.position = 0;
+            self.pending.clear();
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Reads whatever was written to the old file before it was rotated.
+    fn drain(&mut self) -> io::Result<()> {
+        if let Some(reader) = self.reader.as_mut() {
+            io::Read::read_to_end(reader, &mut self.pending)?;
+            for line in self.pending.split_inclusive(|b| *b == b'\n') {
+                let mut line = String::from_utf8_lossy(line).to_string();
+                if !line.ends_with('\n') {
+                    line.push('\n');
+                }
+                self.backlog.push_back(line);
+            }
+            self.pending.clear();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn file_identity(metadata: &Metadata) -> Option<FileIdentity> {
+    use std::os::unix::fs::MetadataExt;
+    Some(FileIdentity(metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_identity(metadata: &Metadata) -> Option<FileIdentity> {
+    let created = metadata.created().ok()?;
+    let since = created.duration_since(std::time::UNIX_EPOCH).ok()?;
+    Some(FileIdentity(since.as_secs(), since.subsec_nanos() as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Write;
+
+    use crate::tail::FileTail;
+
+    fn read_all(tail: &mut FileTail) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut buf = String::new();
+        while tail.read_line(&mut buf).unwrap() > 0 {
+            lines.push(buf.trim_end().to_string());
+            buf.clear();
+        }
+        lines
+    }
+
+    #[test]
+    fn follows_truncation_and_rotation() {
+        let dir = std::env::temp_dir().join(format!("search-tail-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.log");
+        fs::write(&path, "one\ntwo\nthr").unwrap();
+
+        let mut tail = FileTail::new(&path);
+        assert_eq!(read_all(&mut tail), vec!["one", "two"]);
+
+        fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(b"ee\n").unwrap();
+        assert_eq!(read_all(&mut tail), vec!["three"]);
+
+        fs::write(&path, "a\n").unwrap();
+        assert_eq!(read_all(&mut tail), vec!["a"]);
+
+        fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(b"caf\xe9\n").unwrap();
+        assert_eq!(read_all(&mut tail), vec!["caf\u{fffd}"]);
+
+        fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(b"last\n").unwrap();
+        fs::rename(&path, dir.join("app.log.1")).unwrap();
+        fs::write(&path, "new\n").unwrap();
+        assert_eq!(read_all(&mut tail), vec!["last", "new"]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
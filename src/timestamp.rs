@@ -0,0 +1,136 @@
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Tried after the configured ones and RFC3339, the ones without offset are read in the configured timezone
+const FORMATS: [&str; 5] = [
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S,%3f",
+    "%Y-%m-%d %H:%M:%S%.f%z",
+    "%Y-%m-%dT%H:%M:%S%.f%z",
+];
+
+/// `timestamps` in `search.json`
+#[derive(Deserialize, Default)]
+pub struct TimestampConfig {
+    /// chrono formats tried before the built-in ones
+    #[serde(default)]
+    formats: Vec<String>,
+    /// Like `Europe/Berlin`, for timestamps without offset, local time when missing
+    timezone: Option<String>,
+}
+
+struct Settings {
+    formats: Vec<String>,
+    timezone: Option<Tz>,
+}
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+/// Applies the config for all sources, only the first call counts.
+pub fn configure(config: &TimestampConfig) -> Result<(), String> {
+    let timezone = match &config.timezone {
+        Some(name) => { Some(name.parse::<Tz>().map_err(|e| format!("timezone {}: {}", name, e))?) }
+        None => { None }
+    };
+    let _ = SETTINGS.set(Settings { formats: config.formats.clone(), timezone });
+    Ok(())
+}
+
+/// A timestamp as text or as epoch seconds, millis, micros or nanos.
+pub fn parse(value: &Value) -> Option<DateTime<Utc>> {
+    match value {
+        Value::String(s) => { parse_str(s) }
+        Value::Number(n) => {
+            match n.as_i64() {
+                Some(n) => { from_epoch(n) }
+                None => { from_epoch_float(n.as_f64()?) }
+            }
+        }
+        _ => { None }
+    }
+}
+
+pub fn parse_str(s: &str) -> Option<DateTime<Utc>> {
+    let s = s.trim();
+    // Before the epochs, a format like `%Y%m%d%H%M%S` reads digits only too
+    let configured = SETTINGS.get().map(|c| c.formats.as_slice()).unwrap_or_default();
+    if let Some(t) = configured.iter().find_map(|f| parse_with_format(s, f)) {
+        return Some(t);
+    }
+    if let Ok(n) = s.parse::<i64>() {
+        return from_epoch(n);
+    }
+    if let Ok(n) = s.parse::<f64>() {
+        return from_epoch_float(n);
+    }
+    if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+        return Some(t.with_timezone(&Utc));
+    }
+    FORMATS.iter().find_map(|f| parse_with_format(s, f))
+}
+
+/// With an offset in the format it is used, otherwise the configured timezone.
+pub fn parse_with_format(value: &str, format: &str) -> Option<DateTime<Utc>> {
+    match DateTime::parse_from_str(value, format) {
+        Ok(t) => { Some(t.with_timezone(&Utc)) }
+        Err(_) => { in_timezone(&NaiveDateTime::parse_from_str(value, format).ok()?) }
+    }
+}
+
+fn in_timezone(time: &NaiveDateTime) -> Option<DateTime<Utc>> {
+    match SETTINGS.get().and_then(|c| c.timezone) {
+        Some(tz) => { tz.from_local_datetime(time).earliest().map(|t| t.with_timezone(&Utc)) }
+        None => { Local.from_local_datetime(time).earliest().map(|t| t.with_timezone(&Utc)) }
+    }
+}
+
+/// Tells seconds, millis, micros and nanos apart by size, good for dates after 1973.
+fn from_epoch(n: i64) -> Option<DateTime<Utc>> {
+    match n.abs() {
+        0..=99_999_999_999 => { Utc.timestamp_opt(n, 0).single() }
+        100_000_000_000..=99_999_999_999_999 => { Utc.timestamp_millis_opt(n).single() }
+        100_000_000_000_000..=99_999_999_999_999_999 => { Utc.timestamp_opt(n / 1_000_000, (n % 1_000_000) as u32 * 1000).single() }
+        _ => { Some(Utc.timestamp_nanos(n)) }
+    }
+}
+
+/// zap writes seconds with a fraction
+fn from_epoch_float(n: f64) -> Option<DateTime<Utc>> {
+    let millis = match n.abs() > 100_000_000_000.0 {
+        true => { n }
+        false => { n * 1000.0 }
+    };
+    Utc.timestamp_millis_opt(millis as i64).single()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, TimeZone, Utc};
+    use serde_json::json;
+
+    use crate::timestamp::{configure, parse, TimestampConfig};
+
+    #[test]
+    fn parses_epochs_and_formats() {
+        configure(&TimestampConfig { formats: vec!["%Y%m%d%H%M%S".to_string()], timezone: None }).unwrap();
+        let expected = Utc.ymd(2022, 8, 7).and_hms_milli(2, 10, 21, 123);
+        assert_eq!(parse(&json!(1659838221)), Some(Utc.ymd(2022, 8, 7).and_hms(2, 10, 21)));
+        assert_eq!(parse(&json!(1659838221.123)), Some(expected));
+        assert_eq!(parse(&json!(1659838221123u64)), Some(expected));
+        assert_eq!(parse(&json!("1659838221123000")), Some(expected));
+        assert_eq!(parse(&json!(1659838221123000000u64)), Some(expected));
+        assert_eq!(parse(&json!("2022-08-07T04:10:21.123+02:00")), Some(expected));
+        assert_eq!(parse(&json!("2022-08-07 04:10:21.123 +0200")), Some(expected));
+        let local = Local.ymd(2022, 8, 7).and_hms_milli(2, 10, 21, 123).with_timezone(&Utc);
+        assert_eq!(parse(&json!("2022-08-07 02:10:21,123")), Some(local));
+        assert_eq!(parse(&json!("2022-08-07T02:10:21.123")), Some(local));
+        assert_eq!(parse(&json!("2022-08-07T02:10:21")), Some(local - chrono::Duration::milliseconds(123)));
+        assert_eq!(parse(&json!("20220807021021")), Some(local - chrono::Duration::milliseconds(123)));
+        assert_eq!(parse(&json!("yesterday")), None);
+    }
+}